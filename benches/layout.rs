@@ -0,0 +1,198 @@
+//! Measures the memory used by the lines of a large colored output and how
+//! long laying them out takes, against laying out a `TextFormat` per
+//! character like `Full` used to.
+//!
+//! `cargo bench --bench layout`
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use egui::text::LayoutJob;
+use egui_ansi::{
+    Config, Terminal,
+    kind::{TerminalKind, basic::Basic, full::Full, scrollback::ScrollbackLimit},
+};
+
+const LINES: usize = 20_000;
+
+/// Counts allocations, so the real cost of a layout is reported instead of
+/// an estimate.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+/// Bytes currently allocated, wraps around below zero.
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+fn count(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED.fetch_add(size, Ordering::Relaxed);
+    LIVE.fetch_add(size, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        count(new_size);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Time and allocations taken by a piece of work.
+#[derive(Debug, Default, Clone, Copy)]
+struct Measured {
+    time: Duration,
+    allocations: usize,
+    allocated: usize,
+    /// Allocated and not freed again by the end.
+    retained: isize,
+}
+
+impl fmt::Display for Measured {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?}, {} allocations, {} KiB allocated, {} KiB retained",
+            self.time,
+            self.allocations,
+            self.allocated / 1024,
+            self.retained / 1024,
+        )
+    }
+}
+
+fn measure<R>(work: impl FnOnce() -> R) -> (R, Measured) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let live = LIVE.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let result = work();
+    let measured = Measured {
+        time: start.elapsed(),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        allocated: ALLOCATED.load(Ordering::Relaxed) - allocated,
+        retained: LIVE.load(Ordering::Relaxed).wrapping_sub(live) as isize,
+    };
+    (result, measured)
+}
+
+/// Something like the output of a compiler or `ls --color`, a few colored
+/// words per line.
+fn output() -> Vec<u8> {
+    let mut out = Vec::new();
+    for i in 0..LINES {
+        out.extend_from_slice(
+            format!(
+                "\x1b[1;3{}m{i:>6}\x1b[0m: \x1b[3{}mwarning\x1b[0m unused variable `x{i}` \
+                 \x1b[4min\x1b[24m src/main.rs:{}:{}\n",
+                i % 8,
+                (i / 8) % 8,
+                i % 500,
+                i % 80,
+            )
+            .as_bytes(),
+        );
+    }
+    out
+}
+
+/// The job `Full` used to build before styles were interned, a section
+/// with its own `TextFormat` for every character.
+fn per_char_job(job: &LayoutJob) -> LayoutJob {
+    let mut old = LayoutJob {
+        wrap: job.wrap.clone(),
+        ..Default::default()
+    };
+    for section in &job.sections {
+        for c in job.text[section.byte_range.clone()].chars() {
+            old.append(
+                c.encode_utf8(&mut [0; 4]),
+                section.leading_space,
+                section.format.clone(),
+            );
+        }
+    }
+    old
+}
+
+fn bench<K: TerminalKind>(name: &str, data: &[u8]) {
+    let mut cfg = Config::DARK;
+    cfg.scrollback = ScrollbackLimit::Lines(LINES + 1);
+    let mut term = Terminal::<K>::new_box::<256>(cfg);
+
+    let ((), parse) = measure(|| term.write_bytes(data));
+
+    let lines = term.kind().lines();
+    let chars: usize = lines.iter().map(|line| line.text().chars().count()).sum();
+    let runs: usize = lines.iter().map(|line| line.style_runs()).sum();
+    let heap: usize = lines.iter().map(|line| line.heap_size()).sum();
+
+    let ctx = egui::Context::default();
+    let input = || egui::RawInput {
+        screen_rect: Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(1280.0, 800.0),
+        )),
+        ..Default::default()
+    };
+    let mut baseline = Measured::default();
+    let mut table = Measured::default();
+    let mut rows = 0;
+    _ = ctx.run(input(), |ctx| {
+        let job = term.layout(ctx);
+        // the first layout also rasterizes the glyphs, and egui caches
+        // galleys by their job, so warm up with one that hashes differently
+        let mut warm_up = job.clone();
+        warm_up.halign = egui::Align::RIGHT;
+        _ = ctx.fonts(|f| f.layout_job(warm_up));
+
+        (_, baseline) = measure(|| ctx.fonts(|f| f.layout_job(per_char_job(&job))));
+        (rows, table) = measure(|| ctx.fonts(|f| f.layout_job(term.layout(ctx)).rows.len()));
+    });
+
+    // the row cache is empty on the first frame and reused on the second
+    let mut frames = Vec::new();
+    for _ in 0..2 {
+        _ = ctx.run(input(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                frames.push(measure(|| term.show_bordered(ui)).1);
+            });
+        });
+    }
+
+    println!("{name}");
+    println!("  parse:          {parse}");
+    println!("  per char job:   {baseline}");
+    println!("  style table:    {table} ({rows} rows)");
+    println!("  row cache cold: {}", frames[0]);
+    println!("  row cache warm: {}", frames[1]);
+    println!("  characters:     {chars}");
+    println!(
+        "  style runs:     {runs}, {} distinct styles",
+        term.kind().styles().len()
+    );
+    println!("  lines:          {} KiB", heap / 1024);
+}
+
+fn main() {
+    let data = output();
+    bench::<Basic>("basic", &data);
+    bench::<Full>("full", &data);
+}
@@ -22,6 +22,13 @@ impl eframe::App for MyApp {
                 if ui.button("print").clicked() {
                     _ = print_table(&mut &mut *self.term);
                 }
+                if ui.button("print from thread").clicked() {
+                    let mut handle = self.term.handle();
+                    std::thread::spawn(move || print_table(&mut handle));
+                }
+                if ui.button("find").clicked() {
+                    self.term.search.bar_open = !self.term.search.bar_open;
+                }
             });
             self.term.show_bordered(ui);
         });
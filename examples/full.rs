@@ -9,9 +9,13 @@ struct MyApp {
 impl Default for MyApp {
     fn default() -> Self {
         let mut config = egui_ansi::Config::DARK;
-        config.max_rows = 24;
-        config.max_columns = 80;
-        let term = Terminal::new_box::<256>(config);
+        config.rows = 24;
+        config.columns = 80;
+        config.fit_to_widget = true;
+        config.renderer = egui_ansi::Renderer::Grid;
+        let mut term = Terminal::new_box::<256>(config);
+        // the demo output ends its lines with a bare LF
+        term.write_bytes(b"\x1b[20h");
         let mut data = VecDeque::new();
         _ = print_table(&mut &mut data);
         Self { term, data }
@@ -23,6 +27,9 @@ impl eframe::App for MyApp {
         if let Some(byte) = self.data.pop_front() {
             self.term.write_bytes(&[byte]);
         }
+        // no program is attached, echo typed input back into the terminal
+        let typed = self.term.take_output();
+        self.term.write_bytes(&typed);
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("full");
 
@@ -0,0 +1,46 @@
+use eframe::egui;
+use egui_ansi::{Terminal, kind::basic::Basic, pty::PtySession};
+
+struct MyApp {
+    term: Box<Terminal<Basic>>,
+    session: Option<PtySession>,
+}
+
+impl MyApp {
+    fn new(ctx: &egui::Context) -> Self {
+        let mut config = egui_ansi::Config::DARK;
+        config.rows = 24;
+        config.columns = 80;
+        config.fit_to_widget = true;
+        let term = Terminal::new_box::<256>(config);
+        let session = PtySession::shell(24, 80, Some(ctx.clone())).ok();
+        Self { term, session }
+    }
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+        if let Some(session) = &mut self.session
+            && (session.pump(&mut self.term).is_err() || session.is_eof())
+        {
+            self.session = None;
+        }
+        if let Some(title) = self.term.take_title_change() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.to_owned()));
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("pty");
+            self.term.show_bordered(ui);
+        });
+    }
+}
+
+fn main() {
+    let options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "pty",
+        options,
+        Box::new(|cc| Ok(Box::new(MyApp::new(&cc.egui_ctx)))),
+    )
+    .unwrap();
+}
@@ -0,0 +1,108 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::handle::TerminalHandle;
+
+/// Writers wait once this many bytes are queued up for the terminal.
+pub const DEFAULT_LIMIT: usize = 1 << 20;
+
+/// An async sink over a [`TerminalHandle`]. Writes stay pending while the
+/// terminal hasn't caught up with what was written before, which only
+/// happens while it's being shown.
+#[derive(Debug, Clone)]
+pub struct TerminalWriter {
+    handle: TerminalHandle,
+    limit: usize,
+}
+
+impl TerminalWriter {
+    pub fn new(handle: TerminalHandle) -> Self {
+        Self::with_limit(handle, DEFAULT_LIMIT)
+    }
+
+    /// A writer which waits once `limit` bytes are queued.
+    pub fn with_limit(handle: TerminalHandle, limit: usize) -> Self {
+        Self {
+            handle,
+            limit: limit.max(1),
+        }
+    }
+
+    pub fn handle(&self) -> &TerminalHandle {
+        &self.handle
+    }
+
+    fn poll_write_inner(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if self.handle.queued() >= self.limit {
+            self.handle.wake_when_drained(cx.waker());
+            // the queue might have been drained before the waker was stored
+            if self.handle.queued() >= self.limit {
+                return Poll::Pending;
+            }
+        }
+        let len = buf.len().min(self.limit);
+        self.handle.write(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for TerminalWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_inner(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "futures")]
+impl futures::io::AsyncWrite for TerminalWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_inner(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Copies `reader` into the terminal behind `handle` until EOF, returning
+/// the number of bytes copied.
+#[cfg(feature = "tokio")]
+pub async fn pump_tokio(
+    mut reader: impl tokio::io::AsyncRead + Unpin,
+    handle: TerminalHandle,
+) -> io::Result<u64> {
+    tokio::io::copy(&mut reader, &mut TerminalWriter::new(handle)).await
+}
+
+/// Copies `reader` into the terminal behind `handle` until EOF, returning
+/// the number of bytes copied.
+#[cfg(feature = "futures")]
+pub async fn pump_futures(
+    reader: impl futures::io::AsyncRead + Unpin,
+    handle: TerminalHandle,
+) -> io::Result<u64> {
+    futures::io::copy(reader, &mut TerminalWriter::new(handle)).await
+}
@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use egui::Color32;
+
+use crate::Config;
+
+type OnBell = Box<dyn FnMut() + Send>;
+
+/// Bells rung by the program, for flashing the terminal and badges on
+/// terminals that aren't being looked at.
+#[derive(Default)]
+pub(crate) struct Bell {
+    /// Bells since the last [`Bell::acknowledge`].
+    unseen: u64,
+    last: Option<Instant>,
+    on_bell: Option<OnBell>,
+}
+
+impl std::fmt::Debug for Bell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bell")
+            .field("unseen", &self.unseen)
+            .field("last", &self.last)
+            .field("on_bell", &self.on_bell.is_some())
+            .finish()
+    }
+}
+
+impl Bell {
+    pub(crate) fn set_on_bell(&mut self, on_bell: impl FnMut() + Send + 'static) {
+        self.on_bell = Some(Box::new(on_bell));
+    }
+
+    pub(crate) fn unseen(&self) -> u64 {
+        self.unseen
+    }
+
+    pub(crate) fn acknowledge(&mut self) {
+        self.unseen = 0;
+    }
+
+    /// Rings the bell, unless it already rang less than
+    /// `cfg.bell_min_interval_seconds` ago.
+    pub(crate) fn ring(&mut self, cfg: &Config) {
+        let now = Instant::now();
+        // `max` also turns NaN into zero, too long intervals never ring again
+        let min_interval = Duration::try_from_secs_f32(cfg.bell_min_interval_seconds.max(0.0))
+            .unwrap_or(Duration::MAX);
+        if self.last.is_some_and(|last| now - last < min_interval) {
+            return;
+        }
+        self.last = Some(now);
+        self.unseen += 1;
+        if let Some(on_bell) = &mut self.on_bell {
+            on_bell();
+        }
+    }
+
+    /// Paints the visual bell over `rect` while it's fading out.
+    pub(crate) fn flash(&self, ui: &egui::Ui, rect: egui::Rect, cfg: &Config) {
+        let Some(last) = self.last else {
+            return;
+        };
+        if cfg.bell_flash == Color32::TRANSPARENT || cfg.bell_flash_seconds <= 0.0 {
+            return;
+        }
+        let fade = 1.0 - last.elapsed().as_secs_f32() / cfg.bell_flash_seconds;
+        if fade <= 0.0 {
+            return;
+        }
+        ui.painter()
+            .rect_filled(rect, 0.0, cfg.bell_flash.gamma_multiply(fade));
+        ui.ctx().request_repaint();
+    }
+}
@@ -0,0 +1,175 @@
+use crate::kind::sequence::Osc;
+
+/// What programs may do with the clipboard through OSC 52.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardPolicy {
+    #[default]
+    Deny,
+    WriteOnly,
+    ReadWrite,
+    /// Decide per request with the callback set through
+    /// [`GenericTerminal::on_clipboard_request`](crate::GenericTerminal::on_clipboard_request),
+    /// requests are denied without one.
+    Ask,
+}
+
+/// A program asking to use the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardRequest<'a> {
+    Write(&'a str),
+    Read,
+}
+
+type Ask = Box<dyn FnMut(&ClipboardRequest<'_>) -> bool + Send>;
+
+/// OSC 52 state of a terminal.
+///
+/// egui can't read the system clipboard, so queries are answered with the
+/// last text the terminal copied or had pasted into it.
+#[derive(Default)]
+pub(crate) struct Clipboard {
+    copy: Option<String>,
+    last: Option<String>,
+    ask: Option<Ask>,
+}
+
+impl std::fmt::Debug for Clipboard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Clipboard")
+            .field("copy", &self.copy)
+            .field("ask", &self.ask.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Clipboard {
+    pub(crate) fn set_ask(
+        &mut self,
+        ask: impl FnMut(&ClipboardRequest<'_>) -> bool + Send + 'static,
+    ) {
+        self.ask = Some(Box::new(ask));
+    }
+
+    /// Remembers text pasted into the terminal as the clipboard contents.
+    pub(crate) fn remember(&mut self, text: &str) {
+        self.last = Some(text.to_owned());
+    }
+
+    /// Text to put on the clipboard next frame.
+    pub(crate) fn take_copy(&mut self) -> Option<String> {
+        self.copy.take()
+    }
+
+    /// Handles `OSC 52 ; Pc ; Pd ST`, writing the reply to a query into
+    /// `output`.
+    pub(crate) fn osc(&mut self, osc: &Osc<'_>, policy: ClipboardPolicy, output: &mut Vec<u8>) {
+        let Some((selection, data)) = osc.data_str().and_then(|data| data.split_once(';')) else {
+            return;
+        };
+
+        if data == "?" {
+            if !self.allowed(&ClipboardRequest::Read, policy) {
+                return;
+            }
+            let contents = self.last.as_deref().unwrap_or_default();
+            output.extend_from_slice(b"\x1b]52;");
+            output.extend_from_slice(selection.as_bytes());
+            output.push(b';');
+            output.extend_from_slice(encode_base64(contents.as_bytes()).as_bytes());
+            output.extend_from_slice(b"\x1b\\");
+            return;
+        }
+
+        let Some(text) = decode_base64(data).and_then(|bytes| String::from_utf8(bytes).ok()) else {
+            return;
+        };
+        if self.allowed(&ClipboardRequest::Write(&text), policy) {
+            self.last = Some(text.clone());
+            self.copy = Some(text);
+        }
+    }
+
+    fn allowed(&mut self, request: &ClipboardRequest<'_>, policy: ClipboardPolicy) -> bool {
+        match policy {
+            ClipboardPolicy::Deny => false,
+            ClipboardPolicy::WriteOnly => matches!(request, ClipboardRequest::Write(_)),
+            ClipboardPolicy::ReadWrite => true,
+            ClipboardPolicy::Ask => self.ask.as_mut().is_some_and(|ask| ask(request)),
+        }
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut n = 0u32;
+    let mut bits = 0;
+    for b in text.bytes().filter(|b| !b.is_ascii_whitespace()) {
+        if b == b'=' {
+            break;
+        }
+        let value = BASE64.iter().position(|c| *c == b)? as u32;
+        n = n << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for len in 0..8 {
+            let encoded = encode_base64(&bytes[..len]);
+            assert_eq!(decode_base64(&encoded).as_deref(), Some(&bytes[..len]));
+        }
+        let encoded = encode_base64(&bytes);
+        assert_eq!(decode_base64(&encoded), Some(bytes));
+    }
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foo"), "Zm9v");
+        assert_eq!(encode_base64(b"foob"), "Zm9vYg==");
+        assert_eq!(decode_base64("Zm9vYg==").as_deref(), Some(&b"foob"[..]));
+        // padding and line breaks are optional
+        assert_eq!(decode_base64("Zm9vYg").as_deref(), Some(&b"foob"[..]));
+        assert_eq!(decode_base64("Zm9v\r\nYg==").as_deref(), Some(&b"foob"[..]));
+    }
+
+    #[test]
+    fn base64_rejects_invalid_input() {
+        assert_eq!(decode_base64("Zm9v!"), None);
+        assert_eq!(decode_base64("Zm-v"), None);
+    }
+}
@@ -1,13 +1,31 @@
-use egui::Color32;
+use egui::{Color32, FontId, Vec2};
 
-#[derive(Debug)]
+use crate::{clipboard::ClipboardPolicy, kind::scrollback::ScrollbackLimit};
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Config {
     pub font_size: f32,
     pub subscript_font_size: f32,
     pub superscript_font_size: f32,
 
-    pub max_rows: usize,
-    pub max_columns: usize,
+    /// The size of the screen, which `Full` moves the cursor around in and
+    /// both kinds wrap lines at.
+    ///
+    /// These used to be `max_rows` and `max_columns`, where `max_rows` was
+    /// how many lines `Basic` kept. That is [`Config::scrollback`] now.
+    pub rows: usize,
+    pub columns: usize,
+    pub scrollback: ScrollbackLimit,
+    /// Derive the rows/columns of the terminal from the space the widget gets
+    /// instead of `rows`/`columns`.
+    pub fit_to_widget: bool,
+    pub renderer: Renderer,
+    pub emoji_width: EmojiWidth,
+    /// Draw control characters which would otherwise be ignored as their
+    /// symbols from the Control Pictures block, for debugging.
+    pub show_control_pictures: bool,
+    /// Whether programs may set and query the clipboard through OSC 52.
+    pub clipboard: ClipboardPolicy,
 
     pub expand_bg: f32,
 
@@ -37,6 +55,117 @@ pub struct Config {
     pub bright_white: Color32,
     pub slow_blink_time_seconds: f32,
     pub fast_blink_time_seconds: f32,
+
+    pub search_match: Color32,
+    pub search_current_match: Color32,
+
+    /// Painted over the terminal when the bell rings, fading out over
+    /// `bell_flash_seconds`. Transparent turns the visual bell off.
+    pub bell_flash: Color32,
+    pub bell_flash_seconds: f32,
+    /// Bells ringing sooner than this after the last one are ignored, zero,
+    /// negative and NaN let every bell through.
+    pub bell_min_interval_seconds: f32,
+}
+
+/// How many cells emoji made of several characters take up, like ones with
+/// a variation selector or skin tone, ZWJ sequences and flags.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EmojiWidth {
+    /// Two cells, the way they are drawn by most fonts.
+    #[default]
+    Wide,
+    /// The width of their first character, what programs counting with
+    /// `wcwidth` expect.
+    Narrow,
+}
+
+/// How the rows of a terminal are drawn.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Renderer {
+    /// Lay rows out as egui text, which keeps text selectable.
+    #[default]
+    Text,
+    /// Paint every cell directly on a fixed grid. Wide characters take up
+    /// two cells and box drawing characters are drawn as shapes.
+    Grid,
+}
+
+/// One of the 16 colors of the [`Config`] palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteColor {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+}
+
+impl PaletteColor {
+    /// The SGR parameter selecting this color as the foreground.
+    pub fn foreground_sgr(self) -> u8 {
+        let index = self as u8;
+        if index < 8 { 30 + index } else { 90 + index - 8 }
+    }
+}
+
+/// Palette colors log levels are written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelColors {
+    pub error: PaletteColor,
+    pub warn: PaletteColor,
+    pub info: PaletteColor,
+    pub debug: PaletteColor,
+    pub trace: PaletteColor,
+}
+
+impl Default for LevelColors {
+    fn default() -> Self {
+        Self {
+            error: PaletteColor::BrightRed,
+            warn: PaletteColor::BrightYellow,
+            info: PaletteColor::Green,
+            debug: PaletteColor::Blue,
+            trace: PaletteColor::BrightBlack,
+        }
+    }
+}
+
+/// A log level of either `log` or `tracing`.
+#[cfg(any(feature = "log", feature = "tracing"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Severity {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[cfg(any(feature = "log", feature = "tracing"))]
+impl LevelColors {
+    /// The SGR foreground parameter and padded name `severity` is written
+    /// with.
+    pub(crate) fn level(&self, severity: Severity) -> (u8, &'static str) {
+        match severity {
+            Severity::Error => (self.error.foreground_sgr(), "ERROR"),
+            Severity::Warn => (self.warn.foreground_sgr(), " WARN"),
+            Severity::Info => (self.info.foreground_sgr(), " INFO"),
+            Severity::Debug => (self.debug.foreground_sgr(), "DEBUG"),
+            Severity::Trace => (self.trace.foreground_sgr(), "TRACE"),
+        }
+    }
 }
 
 const fn color(raw: u32) -> Color32 {
@@ -44,13 +173,25 @@ const fn color(raw: u32) -> Color32 {
 }
 
 impl Config {
+    /// The size of a single monospace cell at `font_size`.
+    pub fn cell_size(&self, ctx: &egui::Context) -> Vec2 {
+        let font = FontId::monospace(self.font_size);
+        ctx.fonts(|f| Vec2::new(f.glyph_width(&font, 'M'), f.row_height(&font)))
+    }
+
     pub const DARK: Self = Self {
         font_size: 14.0,
         subscript_font_size: 10.0,
         superscript_font_size: 10.0,
 
-        max_rows: 1000,
-        max_columns: usize::MAX,
+        rows: 24,
+        columns: usize::MAX,
+        scrollback: ScrollbackLimit::Lines(1000),
+        fit_to_widget: false,
+        renderer: Renderer::Text,
+        emoji_width: EmojiWidth::Wide,
+        show_control_pictures: false,
+        clipboard: ClipboardPolicy::Deny,
         expand_bg: 0.0,
 
         strike_through_width: 1.0,
@@ -79,5 +220,12 @@ impl Config {
         bright_white: Color32::from_gray(0xf2),
         slow_blink_time_seconds: 1.0,
         fast_blink_time_seconds: 0.5,
+
+        search_match: color(0x614d00),
+        search_current_match: color(0xb56a00),
+
+        bell_flash: Color32::from_rgba_premultiplied(0x30, 0x30, 0x30, 0x30),
+        bell_flash_seconds: 0.15,
+        bell_min_interval_seconds: 0.2,
     };
 }
@@ -0,0 +1,328 @@
+use std::ops::Range;
+
+use egui::{Color32, Painter, Pos2, Rect, Stroke, TextFormat, Vec2, pos2, text::LayoutJob};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    Config,
+    kind::{
+        line::{Line, cluster_width},
+        render::{self, Blink},
+        style::StyleTable,
+    },
+};
+
+/// What to draw on top of a row besides its own contents.
+pub(crate) struct RowDecorations<'a> {
+    pub(crate) cursor: Option<usize>,
+    pub(crate) highlights: &'a [(Range<usize>, bool)],
+    pub(crate) underline: &'a [Range<usize>],
+    pub(crate) blink: Blink,
+    /// DECSCNM, see [`render::reverse_screen`].
+    pub(crate) reverse: bool,
+}
+
+/// Paints `line` as a grid of `cell` sized cells starting at `origin`.
+/// Returns which blink speeds the line uses.
+pub(crate) fn paint_row(
+    painter: &Painter,
+    origin: Pos2,
+    cell: Vec2,
+    line: &Line,
+    styles: &StyleTable,
+    decorations: RowDecorations<'_>,
+    cfg: &Config,
+) -> Blink {
+    let mut used = Blink::default();
+    let mut column = 0;
+    let mut previous = origin;
+    // pending background run, painted once the color changes
+    let mut run: Option<(Rect, Color32)> = None;
+    let mut glyphs = Vec::new();
+
+    let mut sections = line.sections.iter();
+    let mut section_end = 0;
+    let mut format = TextFormat::default();
+    for (byte, cluster) in line.text.grapheme_indices(true) {
+        // a cluster split across styles is drawn in the style it starts with
+        while byte >= section_end
+            && let Some(section) = sections.next()
+        {
+            section_end += section.size;
+            format = styles.get(section.style).clone();
+            if format.line_height == Some(0.0) {
+                used.slow = true;
+                if decorations.blink.slow {
+                    std::mem::swap(&mut format.background, &mut format.color);
+                }
+            } else if format.line_height == Some(1.0) {
+                used.fast = true;
+                if decorations.blink.fast {
+                    std::mem::swap(&mut format.background, &mut format.color);
+                }
+            }
+            if decorations.reverse {
+                render::reverse_screen(&mut format, cfg);
+            }
+        }
+
+        let width = cluster_width(cluster, cfg.emoji_width);
+        let pos = if width == 0 {
+            // lone combining characters go on top of the previous cell
+            previous
+        } else {
+            origin + Vec2::new(column as f32 * cell.x, 0.0)
+        };
+        let size = Vec2::new(width.max(1) as f32 * cell.x, cell.y);
+        let rect = Rect::from_min_size(pos, size);
+
+        let mut color = format.color;
+        let mut background = format.background;
+        if let Some((_, current)) = decorations
+            .highlights
+            .iter()
+            .find(|(range, _)| range.contains(&byte))
+        {
+            background = if *current {
+                cfg.search_current_match
+            } else {
+                cfg.search_match
+            };
+        }
+        if decorations.cursor == Some(byte) {
+            color = cfg.bg_default;
+            background = cfg.fg_default;
+        }
+
+        let mut format = format.clone();
+        if decorations
+            .underline
+            .iter()
+            .any(|range| range.contains(&byte))
+        {
+            format.underline = Stroke::new(cfg.underline_width, color);
+        }
+
+        if width != 0 {
+            run = extend_run(painter, run, rect, background, cfg);
+            column += width;
+            previous = pos;
+        }
+        glyphs.push((cluster, rect, color, format));
+    }
+
+    if let Some(cursor) = decorations.cursor
+        && cursor >= line.text.len()
+    {
+        let rect = Rect::from_min_size(origin + Vec2::new(column as f32 * cell.x, 0.0), cell);
+        run = extend_run(painter, run, rect, cfg.fg_default, cfg);
+    }
+    if let Some((rect, color)) = run {
+        paint_background(painter, rect, color, cfg);
+    }
+
+    for (cluster, rect, color, format) in glyphs {
+        paint_glyph(painter, cluster, rect, color, &format);
+    }
+    used
+}
+
+fn extend_run(
+    painter: &Painter,
+    run: Option<(Rect, Color32)>,
+    rect: Rect,
+    color: Color32,
+    cfg: &Config,
+) -> Option<(Rect, Color32)> {
+    match run {
+        Some((current, current_color)) if current_color == color => {
+            Some((current.union(rect), color))
+        }
+        Some((current, current_color)) => {
+            paint_background(painter, current, current_color, cfg);
+            Some((rect, color))
+        }
+        None => Some((rect, color)),
+    }
+}
+
+fn paint_background(painter: &Painter, rect: Rect, color: Color32, cfg: &Config) {
+    if color != Color32::TRANSPARENT && color != cfg.bg_default {
+        painter.rect_filled(rect, 0.0, color);
+    }
+}
+
+fn paint_glyph(painter: &Painter, cluster: &str, rect: Rect, color: Color32, format: &TextFormat) {
+    let mut chars = cluster.chars();
+    let single = chars.next().filter(|_| chars.next().is_none());
+    if cluster.chars().all(|c| c.is_whitespace() || c.is_control()) || color == Color32::TRANSPARENT
+    {
+        // nothing to draw but decorations
+    } else if !single.is_some_and(|c| paint_box_drawing(painter, c, rect, color)) {
+        let mut job = LayoutJob::single_section(
+            cluster.to_owned(),
+            TextFormat {
+                font_id: format.font_id.clone(),
+                color,
+                italics: format.italics,
+                ..Default::default()
+            },
+        );
+        job.wrap.max_width = f32::INFINITY;
+        let galley = painter.layout_job(job);
+        let y = match format.valign {
+            egui::Align::Min => rect.top(),
+            egui::Align::Max => rect.bottom() - galley.size().y,
+            egui::Align::Center => rect.center().y - galley.size().y / 2.0,
+        };
+        let x = rect.center().x - galley.size().x / 2.0;
+        painter.galley(pos2(x, y), galley, color);
+    }
+
+    if format.underline != Stroke::NONE {
+        let y = rect.bottom() - format.underline.width / 2.0;
+        painter.line_segment(
+            [pos2(rect.left(), y), pos2(rect.right(), y)],
+            Stroke::new(format.underline.width, format.underline.color),
+        );
+    }
+    if format.strikethrough != Stroke::NONE {
+        let y = rect.center().y;
+        painter.line_segment(
+            [pos2(rect.left(), y), pos2(rect.right(), y)],
+            format.strikethrough,
+        );
+    }
+}
+
+/// Line weights of the arms of U+2500 to U+257F, as up, right, down, left.
+/// `l` is light, `h` heavy, `d` double and `.` none.
+const BOX_DRAWING: &[u8; 128 * 4] = b"\
+.l.l.h.hl.l.h.h..l.l.h.hl.l.h.h..l.l.h.hl.l.h.h..ll..hl..lh..hh.\
+..ll..lh..hl..hhll..lh..hl..hh..l..ll..hh..lh..hlll.lhl.hll.llh.\
+hlh.hhl.lhh.hhh.l.lll.lhh.lll.hlh.hlh.lhl.hhh.hh.lll.llh.hll.hlh\
+.lhl.lhh.hhl.hhhll.lll.hlh.llh.hhl.lhl.hhh.lhh.hlllllllhlhlllhlh\
+hlllllhlhlhlhllhhhllllhhlhhlhhlhlhhhhlhhhhhlhhhh.l.l.h.hl.l.h.h.\
+.d.dd.d..dl..ld..dd...ld..dl..ddld..dl..dd..l..dd..ld..dldl.dld.\
+ddd.l.ldd.dld.dd.dld.ldl.dddld.ddl.ldd.dldlddldldddd.ll...lll..l\
+ll.................ll....l....l....hh....h....h..h.ll.h..l.hh.l.";
+
+/// Draws box drawing and block element characters procedurally so they
+/// connect seamlessly across cells. Returns `false` for any other character.
+fn paint_box_drawing(painter: &Painter, c: char, rect: Rect, color: Color32) -> bool {
+    let code = c as u32;
+    match code {
+        0x2571..=0x2573 => {
+            let stroke = Stroke::new((rect.width() / 8.0).max(1.0), color);
+            if code != 0x2572 {
+                painter.line_segment([rect.left_bottom(), rect.right_top()], stroke);
+            }
+            if code != 0x2571 {
+                painter.line_segment([rect.left_top(), rect.right_bottom()], stroke);
+            }
+            true
+        }
+        0x2500..=0x257f => {
+            let at = (code - 0x2500) as usize * 4;
+            let arms = &BOX_DRAWING[at..at + 4];
+            paint_arms(painter, rect, color, [arms[0], arms[1], arms[2], arms[3]]);
+            true
+        }
+        0x2580..=0x259f => {
+            paint_block(painter, code, rect, color);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn paint_arms(painter: &Painter, rect: Rect, color: Color32, arms: [u8; 4]) {
+    let light = (rect.width() / 8.0).max(1.0).round();
+    let center = rect.center();
+    for (i, weight) in arms.into_iter().enumerate() {
+        let (offsets, width): (&[f32], f32) = match weight {
+            b'l' => (&[0.0], light),
+            b'h' => (&[0.0], light * 2.0),
+            b'd' => (&[-light, light], light),
+            _ => continue,
+        };
+        for offset in offsets {
+            let half = width / 2.0;
+            // arms overlap the center so corners and joints are filled in
+            let arm = match i {
+                0 => Rect::from_min_max(
+                    pos2(center.x + offset - half, rect.top()),
+                    pos2(center.x + offset + half, center.y + light + half),
+                ),
+                1 => Rect::from_min_max(
+                    pos2(center.x - light - half, center.y + offset - half),
+                    pos2(rect.right(), center.y + offset + half),
+                ),
+                2 => Rect::from_min_max(
+                    pos2(center.x + offset - half, center.y - light - half),
+                    pos2(center.x + offset + half, rect.bottom()),
+                ),
+                _ => Rect::from_min_max(
+                    pos2(rect.left(), center.y + offset - half),
+                    pos2(center.x + light + half, center.y + offset + half),
+                ),
+            };
+            painter.rect_filled(arm, 0.0, color);
+        }
+    }
+}
+
+fn paint_block(painter: &Painter, code: u32, rect: Rect, color: Color32) {
+    let Rect { min, max } = rect;
+    let (w, h) = (rect.width(), rect.height());
+    let eighths = |n: u32| n as f32 / 8.0;
+    let center = rect.center();
+    let quadrants = |ul: bool, ur: bool, ll: bool, lr: bool| {
+        [
+            (ul, Rect::from_min_max(min, center)),
+            (
+                ur,
+                Rect::from_min_max(pos2(center.x, min.y), pos2(max.x, center.y)),
+            ),
+            (
+                ll,
+                Rect::from_min_max(pos2(min.x, center.y), pos2(center.x, max.y)),
+            ),
+            (lr, Rect::from_min_max(center, max)),
+        ]
+    };
+    let rects: Vec<(bool, Rect)> = match code {
+        0x2580 => vec![(true, Rect::from_min_max(min, pos2(max.x, center.y)))],
+        0x2581..=0x2588 => vec![(
+            true,
+            Rect::from_min_max(pos2(min.x, max.y - h * eighths(code - 0x2580)), max),
+        )],
+        0x2589..=0x258f => vec![(
+            true,
+            Rect::from_min_max(min, pos2(min.x + w * eighths(0x2590 - code), max.y)),
+        )],
+        0x2590 => vec![(true, Rect::from_min_max(pos2(center.x, min.y), max))],
+        0x2591..=0x2593 => {
+            let alpha = (code - 0x2590) as f32 / 4.0;
+            painter.rect_filled(rect, 0.0, color.gamma_multiply(alpha));
+            return;
+        }
+        0x2594 => vec![(true, Rect::from_min_max(min, pos2(max.x, min.y + h / 8.0)))],
+        0x2595 => vec![(true, Rect::from_min_max(pos2(max.x - w / 8.0, min.y), max))],
+        0x2596 => quadrants(false, false, true, false).to_vec(),
+        0x2597 => quadrants(false, false, false, true).to_vec(),
+        0x2598 => quadrants(true, false, false, false).to_vec(),
+        0x2599 => quadrants(true, false, true, true).to_vec(),
+        0x259a => quadrants(true, false, false, true).to_vec(),
+        0x259b => quadrants(true, true, true, false).to_vec(),
+        0x259c => quadrants(true, true, false, true).to_vec(),
+        0x259d => quadrants(false, true, false, false).to_vec(),
+        0x259e => quadrants(false, true, true, false).to_vec(),
+        _ => quadrants(false, true, true, true).to_vec(),
+    };
+    for (fill, rect) in rects {
+        if fill {
+            painter.rect_filled(rect, 0.0, color);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+    task::Waker,
+};
+
+/// State shared between a terminal and its handles.
+#[derive(Debug, Default)]
+pub(crate) struct Shared {
+    queue: Mutex<Vec<u8>>,
+    ctx: OnceLock<egui::Context>,
+    /// Writers waiting for the queue to be drained.
+    waiting: Mutex<Vec<Waker>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+impl Shared {
+    /// Everything written through handles since the last call.
+    pub(crate) fn take(&self) -> Vec<u8> {
+        let pending = std::mem::take(&mut *lock(&self.queue));
+        for waker in lock(&self.waiting).drain(..) {
+            waker.wake();
+        }
+        pending
+    }
+
+    /// Remembers the context the terminal is shown in, so handles know what
+    /// to repaint.
+    pub(crate) fn attach(&self, ctx: &egui::Context) {
+        self.ctx.get_or_init(|| ctx.clone());
+    }
+}
+
+/// Writes bytes into a terminal from any thread. Writes are batched and fed
+/// to the terminal the next time it's shown, which is requested right away.
+#[derive(Debug, Clone)]
+pub struct TerminalHandle {
+    shared: Arc<Shared>,
+}
+
+impl TerminalHandle {
+    pub(crate) fn new(shared: Arc<Shared>) -> Self {
+        Self { shared }
+    }
+
+    pub fn write(&self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        lock(&self.shared.queue).extend_from_slice(bytes);
+        if let Some(ctx) = self.shared.ctx.get() {
+            ctx.request_repaint();
+        }
+    }
+
+    pub fn write_str(&self, s: &str) {
+        self.write(s.as_bytes());
+    }
+
+    /// Bytes written which the terminal hasn't taken in yet.
+    pub fn queued(&self) -> usize {
+        lock(&self.shared.queue).len()
+    }
+
+    /// Wakes `waker` the next time the terminal takes in the queue.
+    pub fn wake_when_drained(&self, waker: &Waker) {
+        let mut waiting = lock(&self.shared.waiting);
+        if !waiting.iter().any(|w| w.will_wake(waker)) {
+            waiting.push(waker.clone());
+        }
+    }
+}
+
+impl std::io::Write for TerminalHandle {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        TerminalHandle::write(self, buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl std::fmt::Write for TerminalHandle {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        TerminalHandle::write_str(self, s);
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use egui::{Key, Modifiers};
+
+use crate::kind::modes::Modes;
+
+const ESC: u8 = 0x1b;
+
+/// xterm's modifier parameter, `1 + shift + 2*alt + 4*ctrl`.
+fn modifier_param(modifiers: Modifiers) -> u8 {
+    1 + modifiers.shift as u8 + 2 * modifiers.alt as u8 + 4 * modifiers.ctrl as u8
+}
+
+fn has_modifiers(modifiers: Modifiers) -> bool {
+    modifiers.shift || modifiers.alt || modifiers.ctrl
+}
+
+/// `ESC [ x` / `ESC O x` style keys (cursor keys, Home/End, F1-F4).
+fn ss3_key(out: &mut Vec<u8>, final_byte: u8, modifiers: Modifiers, application: bool) {
+    if has_modifiers(modifiers) {
+        out.extend_from_slice(&[ESC, b'[', b'1', b';']);
+        out.extend_from_slice(modifier_param(modifiers).to_string().as_bytes());
+        out.push(final_byte);
+    } else if application {
+        out.extend_from_slice(&[ESC, b'O', final_byte]);
+    } else {
+        out.extend_from_slice(&[ESC, b'[', final_byte]);
+    }
+}
+
+/// `ESC [ n ~` style keys (Insert/Delete, Page Up/Down, F5-F20).
+fn tilde_key(out: &mut Vec<u8>, code: u8, modifiers: Modifiers) {
+    out.extend_from_slice(&[ESC, b'[']);
+    out.extend_from_slice(code.to_string().as_bytes());
+    if has_modifiers(modifiers) {
+        out.push(b';');
+        out.extend_from_slice(modifier_param(modifiers).to_string().as_bytes());
+    }
+    out.push(b'~');
+}
+
+fn ctrl_code(key: Key) -> Option<u8> {
+    let name = key.name().as_bytes();
+    if let [c @ b'A'..=b'Z'] = name {
+        return Some(c - b'A' + 1);
+    }
+    Some(match key {
+        Key::Space | Key::Num2 => 0x00,
+        Key::OpenBracket | Key::Num3 => 0x1b,
+        Key::Backslash | Key::Num4 => 0x1c,
+        Key::CloseBracket | Key::Num5 => 0x1d,
+        Key::Num6 => 0x1e,
+        Key::Slash | Key::Minus | Key::Num7 => 0x1f,
+        Key::Num8 => 0x7f,
+        _ => return None,
+    })
+}
+
+/// Appends the bytes a VT220/xterm sends for `key` to `out`. Returns `false`
+/// if the key doesn't produce anything on its own, printable keys are sent
+/// through [`encode_text`] from the matching text event instead.
+pub fn encode_key(key: Key, modifiers: Modifiers, modes: &Modes, out: &mut Vec<u8>) -> bool {
+    let app = modes.application_cursor_keys;
+    match key {
+        Key::ArrowUp => ss3_key(out, b'A', modifiers, app),
+        Key::ArrowDown => ss3_key(out, b'B', modifiers, app),
+        Key::ArrowRight => ss3_key(out, b'C', modifiers, app),
+        Key::ArrowLeft => ss3_key(out, b'D', modifiers, app),
+        Key::Home => ss3_key(out, b'H', modifiers, app),
+        Key::End => ss3_key(out, b'F', modifiers, app),
+
+        Key::F1 => ss3_key(out, b'P', modifiers, true),
+        Key::F2 => ss3_key(out, b'Q', modifiers, true),
+        Key::F3 => ss3_key(out, b'R', modifiers, true),
+        Key::F4 => ss3_key(out, b'S', modifiers, true),
+
+        Key::Insert => tilde_key(out, 2, modifiers),
+        Key::Delete => tilde_key(out, 3, modifiers),
+        Key::PageUp => tilde_key(out, 5, modifiers),
+        Key::PageDown => tilde_key(out, 6, modifiers),
+        Key::F5 => tilde_key(out, 15, modifiers),
+        Key::F6 => tilde_key(out, 17, modifiers),
+        Key::F7 => tilde_key(out, 18, modifiers),
+        Key::F8 => tilde_key(out, 19, modifiers),
+        Key::F9 => tilde_key(out, 20, modifiers),
+        Key::F10 => tilde_key(out, 21, modifiers),
+        Key::F11 => tilde_key(out, 23, modifiers),
+        Key::F12 => tilde_key(out, 24, modifiers),
+        Key::F13 => tilde_key(out, 25, modifiers),
+        Key::F14 => tilde_key(out, 26, modifiers),
+        Key::F15 => tilde_key(out, 28, modifiers),
+        Key::F16 => tilde_key(out, 29, modifiers),
+        Key::F17 => tilde_key(out, 31, modifiers),
+        Key::F18 => tilde_key(out, 32, modifiers),
+        Key::F19 => tilde_key(out, 33, modifiers),
+        Key::F20 => tilde_key(out, 34, modifiers),
+
+        Key::Tab if modifiers.shift => out.extend_from_slice(&[ESC, b'[', b'Z']),
+        Key::Tab | Key::Enter | Key::Backspace | Key::Escape => {
+            if modifiers.alt {
+                out.push(ESC);
+            }
+            out.push(match key {
+                Key::Tab => b'\t',
+                Key::Enter => b'\r',
+                Key::Backspace if modifiers.ctrl => 0x08,
+                Key::Backspace => 0x7f,
+                _ => ESC,
+            });
+            if key == Key::Enter && modes.newline {
+                out.push(b'\n');
+            }
+        }
+
+        _ if modifiers.ctrl => {
+            let Some(code) = ctrl_code(key) else {
+                return false;
+            };
+            if modifiers.alt {
+                out.push(ESC);
+            }
+            out.push(code);
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Appends typed text to `out`, Alt prefixes it with `ESC` like xterm's
+/// `metaSendsEscape`.
+pub fn encode_text(text: &str, modifiers: Modifiers, out: &mut Vec<u8>) {
+    if modifiers.alt && !modifiers.ctrl {
+        out.push(ESC);
+    }
+    out.extend_from_slice(text.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bytes sent for `key`, nothing if it isn't encoded on its own.
+    fn key(key: Key, modifiers: Modifiers, modes: &Modes) -> Vec<u8> {
+        let mut out = Vec::new();
+        let encoded = encode_key(key, modifiers, modes, &mut out);
+        assert_eq!(encoded, !out.is_empty());
+        out
+    }
+
+    #[test]
+    fn arrows_follow_application_cursor_mode() {
+        let mut modes = Modes::new();
+        assert_eq!(key(Key::ArrowUp, Modifiers::NONE, &modes), b"\x1b[A");
+        assert_eq!(key(Key::ArrowLeft, Modifiers::NONE, &modes), b"\x1b[D");
+
+        modes.application_cursor_keys = true;
+        assert_eq!(key(Key::ArrowUp, Modifiers::NONE, &modes), b"\x1bOA");
+        assert_eq!(key(Key::ArrowLeft, Modifiers::NONE, &modes), b"\x1bOD");
+        // modified arrows are the same in both modes
+        assert_eq!(key(Key::ArrowUp, Modifiers::SHIFT, &modes), b"\x1b[1;2A");
+        assert_eq!(
+            key(Key::ArrowRight, Modifiers::CTRL | Modifiers::ALT, &modes),
+            b"\x1b[1;7C"
+        );
+    }
+
+    #[test]
+    fn ctrl_letters_are_c0_controls() {
+        let modes = Modes::new();
+        assert_eq!(key(Key::A, Modifiers::CTRL, &modes), [0x01]);
+        assert_eq!(key(Key::C, Modifiers::CTRL, &modes), [0x03]);
+        assert_eq!(key(Key::Z, Modifiers::CTRL, &modes), [0x1a]);
+        assert_eq!(key(Key::Space, Modifiers::CTRL, &modes), [0x00]);
+        assert_eq!(key(Key::OpenBracket, Modifiers::CTRL, &modes), [0x1b]);
+        // plain letters come from the text event
+        assert_eq!(key(Key::A, Modifiers::NONE, &modes), b"");
+    }
+
+    #[test]
+    fn alt_prefixes_escape() {
+        let modes = Modes::new();
+        let ctrl_alt = Modifiers::CTRL | Modifiers::ALT;
+        assert_eq!(key(Key::A, ctrl_alt, &modes), b"\x1b\x01");
+        assert_eq!(key(Key::Backspace, Modifiers::ALT, &modes), b"\x1b\x7f");
+        assert_eq!(key(Key::Enter, Modifiers::ALT, &modes), b"\x1b\r");
+
+        let mut out = Vec::new();
+        encode_text("x", Modifiers::ALT, &mut out);
+        assert_eq!(out, b"\x1bx");
+        // AltGr shows up as Ctrl+Alt and types the text as is
+        out.clear();
+        encode_text("@", ctrl_alt, &mut out);
+        assert_eq!(out, b"@");
+    }
+
+    #[test]
+    fn enter_sends_cr_lf_with_lnm() {
+        let mut modes = Modes::new();
+        assert_eq!(key(Key::Enter, Modifiers::NONE, &modes), b"\r");
+        modes.newline = true;
+        assert_eq!(key(Key::Enter, Modifiers::NONE, &modes), b"\r\n");
+    }
+
+    #[test]
+    fn editing_and_function_keys() {
+        let modes = Modes::new();
+        assert_eq!(key(Key::Tab, Modifiers::SHIFT, &modes), b"\x1b[Z");
+        assert_eq!(key(Key::Backspace, Modifiers::CTRL, &modes), [0x08]);
+        assert_eq!(key(Key::Delete, Modifiers::NONE, &modes), b"\x1b[3~");
+        assert_eq!(key(Key::PageUp, Modifiers::CTRL, &modes), b"\x1b[5;5~");
+        assert_eq!(key(Key::F1, Modifiers::NONE, &modes), b"\x1bOP");
+        assert_eq!(key(Key::F5, Modifiers::NONE, &modes), b"\x1b[15~");
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    cell::RefCell,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
+
+use crate::kind::{
+    line::{Line, Section},
+    link::LinkId,
+    style::StyleId,
+};
+
+/// Lines are compressed and written out in chunks of this many.
+const CHUNK_LINES: usize = 1024;
+/// Decoded chunks kept around for scrolling back and forth.
+const CACHED_CHUNKS: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+struct Chunk {
+    offset: u64,
+    len: usize,
+    lines: usize,
+}
+
+/// Evicted scrollback, compressed into a temporary file which is removed
+/// again when the archive is dropped.
+#[derive(Debug)]
+pub struct Archive {
+    path: PathBuf,
+    file: RefCell<File>,
+    end: u64,
+    chunks: Vec<Chunk>,
+    /// Lines not yet written out as a full chunk.
+    pending: Vec<Line>,
+    /// Recently decoded chunks, most recently used last.
+    cache: RefCell<Vec<(usize, Vec<Line>)>>,
+}
+
+impl Archive {
+    /// Creates an empty archive in the system's temporary directory.
+    pub fn new() -> io::Result<Self> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "egui-ansi-{}-{}.scrollback",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        // the scrollback may hold anything the program printed, keep it
+        // private to the user
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options.open(&path)?;
+        Ok(Self {
+            path,
+            file: RefCell::new(file),
+            end: 0,
+            chunks: Vec::new(),
+            pending: Vec::new(),
+            cache: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// The number of archived lines.
+    pub fn len(&self) -> usize {
+        self.chunks.len() * CHUNK_LINES + self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes written to disk so far.
+    pub fn disk_size(&self) -> u64 {
+        self.end
+    }
+
+    pub(crate) fn push(&mut self, line: &Line) -> io::Result<()> {
+        self.pending.push(line.clone());
+        if self.pending.len() < CHUNK_LINES {
+            return Ok(());
+        }
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        for line in &self.pending {
+            encode(line, &mut encoder)?;
+        }
+        let data = encoder.finish()?;
+
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(self.end))?;
+        file.write_all(&data)?;
+        self.chunks.push(Chunk {
+            offset: self.end,
+            len: data.len(),
+            lines: self.pending.len(),
+        });
+        self.end += data.len() as u64;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Archived line `index`, reading its chunk back from disk if needed.
+    pub fn get(&self, index: usize) -> Option<Line> {
+        let chunk = index / CHUNK_LINES;
+        let Some(&meta) = self.chunks.get(chunk) else {
+            return self.pending.get(index - self.chunks.len() * CHUNK_LINES).cloned();
+        };
+
+        let mut cache = self.cache.borrow_mut();
+        if let Some(at) = cache.iter().position(|(i, _)| *i == chunk) {
+            let entry = cache.remove(at);
+            cache.push(entry);
+        } else {
+            let lines = self.read(meta).ok()?;
+            if cache.len() == CACHED_CHUNKS {
+                cache.remove(0);
+            }
+            cache.push((chunk, lines));
+        }
+        cache.last()?.1.get(index % CHUNK_LINES).cloned()
+    }
+
+    /// Forgets every archived line.
+    pub(crate) fn clear(&mut self) {
+        self.chunks.clear();
+        self.pending.clear();
+        self.cache.get_mut().clear();
+        self.end = 0;
+        _ = self.file.get_mut().set_len(0);
+    }
+
+    fn read(&self, chunk: Chunk) -> io::Result<Vec<Line>> {
+        let mut data = vec![0; chunk.len];
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(chunk.offset))?;
+        file.read_exact(&mut data)?;
+
+        let mut decoder = DeflateDecoder::new(data.as_slice());
+        (0..chunk.lines).map(|_| decode(&mut decoder)).collect()
+    }
+}
+
+impl Drop for Archive {
+    fn drop(&mut self) {
+        _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn encode(line: &Line, out: &mut impl Write) -> io::Result<()> {
+    out.write_all(&line.revision.to_le_bytes())?;
+    out.write_all(&[line.wrapped as u8])?;
+    out.write_all(&(line.text.len() as u32).to_le_bytes())?;
+    out.write_all(line.text.as_bytes())?;
+    out.write_all(&(line.sections.len() as u32).to_le_bytes())?;
+    for section in &line.sections {
+        out.write_all(&section.style.0.to_le_bytes())?;
+        // `0` for no link, the id plus one otherwise
+        let link = section.link.map_or(0, |link| link.0 + 1);
+        out.write_all(&link.to_le_bytes())?;
+        out.write_all(&(section.size as u32).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn decode(input: &mut impl Read) -> io::Result<Line> {
+    fn u32(input: &mut impl Read) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        input.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    let mut revision = [0; 8];
+    input.read_exact(&mut revision)?;
+    let mut wrapped = [0];
+    input.read_exact(&mut wrapped)?;
+
+    let mut text = vec![0; u32(input)? as usize];
+    input.read_exact(&mut text)?;
+    let text =
+        String::from_utf8(text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    let sections = (0..u32(input)?)
+        .map(|_| {
+            Ok(Section {
+                style: StyleId(u32(input)?),
+                link: u32(input)?.checked_sub(1).map(LinkId),
+                size: u32(input)? as usize,
+            })
+        })
+        .collect::<io::Result<_>>()?;
+
+    Ok(Line {
+        text,
+        sections,
+        wrapped: wrapped[0] != 0,
+        revision: u64::from_le_bytes(revision),
+    })
+}
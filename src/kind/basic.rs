@@ -1,19 +1,32 @@
-use egui::{
-    TextFormat,
-    text::{LayoutJob, TextWrapping},
-};
-
 use crate::{
-    Config,
-    kind::{TerminalKind, style::StyleState},
+    Config, TerminalSize,
+    kind::{
+        TerminalKind,
+        line::{self, Line, Pen, RowPosition},
+        link::LinkTable,
+        modes::Modes,
+        scrollback::Scrollback,
+        sequence::Sequence,
+        shared::{self, Control, Shared},
+        style::{StyleState, StyleTable},
+        title::Title,
+    },
 };
 
 pub struct Basic {
-    layout: LayoutJob,
-    line: usize,
+    lines: Scrollback,
     column: usize,
+    /// Cells taken up by the last line, so writing at its end doesn't have
+    /// to measure it.
+    width: usize,
+    /// Wrapping follows `size.columns`.
+    size: TerminalSize,
 
     style: StyleState,
+    /// The style of cells skipped over by tabs.
+    blank: StyleState,
+    styles: StyleTable,
+    shared: Shared,
 }
 
 impl Basic {
@@ -25,6 +38,10 @@ impl Basic {
                 }
             }
             ansi::KnownCSI::EraseDisplay => self.clear(),
+            ansi::KnownCSI::EnableFocusReporting => self.shared.modes.focus_reporting = true,
+            ansi::KnownCSI::DisableFocusReporting => self.shared.modes.focus_reporting = false,
+            ansi::KnownCSI::EnableBracketPastingMode => self.shared.modes.bracketed_paste = true,
+            ansi::KnownCSI::DisableBracketPastingMode => self.shared.modes.bracketed_paste = false,
             ansi::KnownCSI::SelectGraphicRendition(gr) => {
                 for sg in gr {
                     self.style.sg(sg);
@@ -35,69 +52,69 @@ impl Basic {
     }
 
     fn encounter_char(&mut self, c: char, cfg: &Config) {
-        let format = self.style.format(cfg);
-        use unicode_width::UnicodeWidthChar;
-        let width = c.width().unwrap_or_default();
-        self.column += width;
-        if self.column > cfg.max_columns && c != '\n' {
-            self.insert('\n', format.clone());
+        let pen = Pen {
+            style: self.style.id(&mut self.styles, cfg),
+            link: self.shared.link,
+            blank: self.blank.id(&mut self.styles, cfg),
+        };
+        let column = self.column - 1;
+        let line_width = self.width;
+        let mut attached = None;
+        self.lines.edit_back(|line| {
+            attached = line.attach(column, line_width, c, pen, cfg.emoji_width);
+        });
+        if let Some((_, grown)) = attached {
+            self.column += grown;
+            self.width += grown;
+            return;
         }
-        self.insert(c, format);
 
-        while self.line > cfg.max_rows {
-            self.delete_line();
+        use unicode_width::UnicodeWidthChar;
+        let width = c.width().unwrap_or_default();
+        if self.column - 1 + width > self.size.columns && self.column > 1 {
+            self.new_line(true, cfg);
         }
+        let column = self.column - 1;
+        self.column += width;
+        let mut line_width = self.width;
+        self.lines.edit_back(|line| {
+            (_, line_width) = line.put(column, line_width, c, pen, cfg.emoji_width);
+        });
+        self.width = line_width;
     }
 
-    fn insert(&mut self, c: char, format: TextFormat) {
-        if let Some(last) = self.layout.sections.last_mut()
-            && last.format == format
-        {
-            last.byte_range.end += c.len_utf8();
-            self.layout.text.push(c);
-        } else {
-            let spacing = if self.style.proportional {
-                0.0
-            } else {
-                format.extra_letter_spacing
-            };
-            self.layout
-                .append(c.encode_utf8(&mut [0u8; 4]), spacing, format);
-        }
-        if c == '\n' {
-            self.line += 1;
-            self.column = 1;
+    fn control(&mut self, c0: u8, cfg: &Config) {
+        match self.shared.control(c0, cfg) {
+            Some(Control::LineFeed) => self.new_line(false, cfg),
+            Some(Control::CarriageReturn) => self.column = 1,
+            Some(Control::Backspace) => self.column = self.column.saturating_sub(1).max(1),
+            Some(Control::Tab) => {
+                self.column = shared::next_tab_stop(self.column).min(self.size.columns.max(1));
+            }
+            Some(Control::Picture(c)) => self.encounter_char(c, cfg),
+            None => {}
         }
     }
 
-    fn delete_line(&mut self) {
-        if let Some(at) = self.layout.text.find('\n') {
-            let new = self.layout.text.split_off(at + 1);
-            let cutoff = self.layout.text.len();
-            self.layout.text = new;
-            self.layout.sections.retain_mut(|section| {
-                if section.byte_range.end <= cutoff {
-                    false
-                } else {
-                    section.byte_range.start = section.byte_range.start.saturating_sub(cutoff);
-                    section.byte_range.end = section.byte_range.end.saturating_sub(cutoff);
-                    true
-                }
-            });
-            self.line -= 1;
-        } else {
-            self.clear();
-        }
+    fn new_line(&mut self, wrapped: bool, cfg: &Config) {
+        self.lines.edit_back(|line| line.wrapped = wrapped);
+        self.lines.push_back(Line::default(), cfg.scrollback);
+        self.column = 1;
+        self.width = 0;
     }
 }
 
 impl TerminalKind for Basic {
-    fn new(_: &crate::Config) -> Self {
+    fn new(cfg: &crate::Config) -> Self {
         let mut me = Self {
-            layout: Default::default(),
-            line: 1,
+            lines: Scrollback::new(),
             column: 1,
+            width: 0,
+            size: TerminalSize::from_config(cfg),
             style: StyleState::new(),
+            blank: StyleState::new(),
+            styles: StyleTable::new(),
+            shared: Shared::new(),
         };
         me.clear();
         me
@@ -105,60 +122,76 @@ impl TerminalKind for Basic {
 
     fn march(&mut self, out: ansi::Out<'_>, cfg: &Config) {
         match out {
-            ansi::Out::Data(c) => self.encounter_char(c, cfg),
+            ansi::Out::Data(c) if c.is_ascii_control() => self.control(c as u8, cfg),
+            ansi::Out::Data(c) => {
+                let c = self.shared.charsets.map(c);
+                self.encounter_char(c, cfg);
+            }
             ansi::Out::SP => self.encounter_char(' ', cfg),
             ansi::Out::CSI(csi) => self.csi(csi.parse(), cfg),
-            ansi::Out::C0(c0) => self.encounter_char(c0 as u8 as char, cfg),
+            ansi::Out::C0(c0) => self.control(c0 as u8, cfg),
             _ => {}
         }
     }
 
-    fn layout(&mut self, cfg: &crate::Config, ctx: &egui::Context) -> LayoutJob {
-        let mut clone = self.layout.clone();
+    fn sequence(&mut self, seq: Sequence<'_>, _: &Config) {
+        self.shared.sequence(seq);
+    }
 
-        let slow_enabled = cfg.slow_blink_time_seconds != 0.0;
-        let fast_enabled = cfg.fast_blink_time_seconds != 0.0;
+    fn modes(&self) -> &Modes {
+        &self.shared.modes
+    }
 
-        let time = ctx.input(|i| i.time);
+    fn lines(&self) -> &Scrollback {
+        &self.lines
+    }
 
-        let slow_rem = (time % cfg.slow_blink_time_seconds as f64) as f32;
-        let fast_rem = (time % cfg.fast_blink_time_seconds as f64) as f32;
+    fn scrollback_mut(&mut self) -> &mut Scrollback {
+        &mut self.lines
+    }
 
-        let slow_swap = slow_enabled && slow_rem > cfg.slow_blink_time_seconds / 2.0;
-        let fast_swap = fast_enabled && fast_rem > cfg.fast_blink_time_seconds / 2.0;
+    fn styles(&self) -> &StyleTable {
+        &self.styles
+    }
 
-        let mut slow = false;
-        let mut fast = false;
+    fn links(&self) -> &LinkTable {
+        &self.shared.links
+    }
 
-        for section in &mut clone.sections {
-            if section.format.line_height == Some(0.0) {
-                slow = true;
-                if slow_swap {
-                    std::mem::swap(&mut section.format.background, &mut section.format.color);
-                }
-            } else if section.format.line_height == Some(1.0) {
-                fast = true;
-                if fast_swap {
-                    std::mem::swap(&mut section.format.background, &mut section.format.color);
-                }
-            }
-            section.format.line_height = None;
-        }
-        if slow {
-            let half = cfg.slow_blink_time_seconds / 2.0;
-            ctx.request_repaint_after_secs(half - (time % half as f64) as f32);
-        }
-        if fast {
-            let half = cfg.fast_blink_time_seconds / 2.0;
-            ctx.request_repaint_after_secs(half - (time % half as f64) as f32);
+    fn cursor(&self) -> Option<RowPosition> {
+        None
+    }
+
+    fn title(&self) -> &Title {
+        &self.shared.title
+    }
+
+    fn resize(&mut self, size: TerminalSize, cfg: &Config) {
+        let reflow = self.size.columns != size.columns;
+        self.size = size;
+        if !reflow {
+            return;
         }
-        clone
+        let last = self.lines.len().saturating_sub(1);
+        let mut cursor = RowPosition {
+            row: last,
+            byte: self.lines.back().map_or(0, |line| line.text.len()),
+        };
+        self.lines.edit_all(|lines| {
+            line::reflow(lines, size.columns, cfg.emoji_width, &mut cursor);
+            lines.truncate(cursor.row + 1);
+        });
+        self.width = self
+            .lines
+            .back()
+            .map_or(0, |line| line.width(cfg.emoji_width));
+        self.column = self.width + 1;
+        self.lines.trim(cfg.scrollback);
     }
 
     fn clear(&mut self) {
-        self.line = 1;
         self.column = 1;
-        self.layout = LayoutJob::default();
-        self.layout.wrap = TextWrapping::no_max_width();
+        self.width = 0;
+        self.lines.clear();
     }
 }
@@ -0,0 +1,202 @@
+/// A 94 character set which can be designated into G0–G3.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    #[default]
+    Ascii,
+    /// The line drawing set ncurses draws boxes with.
+    DecSpecialGraphics,
+    Uk,
+    Dutch,
+    Finnish,
+    French,
+    FrenchCanadian,
+    German,
+    Italian,
+    NorwegianDanish,
+    Spanish,
+    Swedish,
+    Swiss,
+}
+
+/// The ASCII characters national replacement sets put something else at.
+const NATIONAL_POSITIONS: &str = "#@[\\]^_`{|}~";
+
+/// `0x5f..=0x7e` in the DEC Special Graphics set.
+const DEC_SPECIAL_GRAPHICS: &str = "\u{a0}◆▒␉␌␍␊°±␤␋┘┐┌└┼⎺⎻─⎼⎽├┤┴┬│≤≥π≠£·";
+
+impl Charset {
+    /// The set selected by the final byte of `ESC ( F` and friends.
+    pub fn from_final_byte(final_byte: u8) -> Option<Self> {
+        Some(match final_byte {
+            b'B' => Self::Ascii,
+            b'0' => Self::DecSpecialGraphics,
+            b'A' => Self::Uk,
+            b'4' => Self::Dutch,
+            b'C' | b'5' => Self::Finnish,
+            b'R' | b'f' => Self::French,
+            b'Q' | b'9' => Self::FrenchCanadian,
+            b'K' => Self::German,
+            b'Y' => Self::Italian,
+            b'E' | b'6' | b'`' => Self::NorwegianDanish,
+            b'Z' => Self::Spanish,
+            b'H' | b'7' => Self::Swedish,
+            b'=' => Self::Swiss,
+            _ => return None,
+        })
+    }
+
+    /// Replacements for [`NATIONAL_POSITIONS`].
+    fn national(self) -> Option<&'static str> {
+        Some(match self {
+            Self::Uk => "£@[\\]^_`{|}~",
+            Self::Dutch => "£¾ĳ½|^_`¨ƒ¼´",
+            Self::Finnish => "#@ÄÖÅÜ_éäöåü",
+            Self::French => "£à°ç§^_`éùè¨",
+            Self::FrenchCanadian => "#àâçêî_ôéùèû",
+            Self::German => "#§ÄÖÜ^_`äöüß",
+            Self::Italian => "£§°çé^_ùàòèì",
+            Self::NorwegianDanish => "#ÄÆØÅÜ_äæøåü",
+            Self::Spanish => "£§¡Ñ¿^_`°ñç~",
+            Self::Swedish => "#ÉÄÖÅÜ_éäöåü",
+            Self::Swiss => "ùàéçêîèôäöüû",
+            Self::Ascii | Self::DecSpecialGraphics => return None,
+        })
+    }
+
+    pub fn map(self, c: char) -> char {
+        if self == Self::DecSpecialGraphics {
+            return match c {
+                '\x5f'..='\x7e' => DEC_SPECIAL_GRAPHICS
+                    .chars()
+                    .nth(c as usize - 0x5f)
+                    .unwrap_or(c),
+                _ => c,
+            };
+        }
+        let Some(national) = self.national() else {
+            return c;
+        };
+        NATIONAL_POSITIONS
+            .chars()
+            .position(|p| p == c)
+            .and_then(|i| national.chars().nth(i))
+            .unwrap_or(c)
+    }
+}
+
+/// The G0–G3 designations and which of them is shifted in.
+#[derive(Debug, Default, Clone)]
+pub struct Charsets {
+    g: [Charset; 4],
+    /// The set invoked into GL by the locking shifts.
+    gl: usize,
+    /// Set by SS2/SS3 for the next character only.
+    single_shift: Option<usize>,
+}
+
+impl Charsets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Translates a printable character through the active set.
+    pub fn map(&mut self, c: char) -> char {
+        let set = self.single_shift.take().unwrap_or(self.gl);
+        self.g[set].map(c)
+    }
+
+    /// Handles SO (`true`) and SI (`false`), invoking G1 or G0.
+    pub fn shift(&mut self, out: bool) {
+        self.gl = if out { 1 } else { 0 };
+    }
+
+    /// Handles designations (`ESC ( F`, `ESC ) F`, `ESC * F`, `ESC + F`),
+    /// locking shifts (`ESC n`, `ESC o`) and single shifts (`ESC N`, `ESC O`).
+    pub fn esc(&mut self, intermediate: Option<u8>, final_byte: u8) {
+        match (intermediate, final_byte) {
+            (Some(g @ b'('..=b'+'), _) => {
+                if let Some(set) = Charset::from_final_byte(final_byte) {
+                    self.g[(g - b'(') as usize] = set;
+                }
+            }
+            (None, b'n') => self.gl = 2,
+            (None, b'o') => self.gl = 3,
+            (None, b'N') => self.single_shift = Some(2),
+            (None, b'O') => self.single_shift = Some(3),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(charsets: &mut Charsets, text: &str) -> String {
+        text.chars().map(|c| charsets.map(c)).collect()
+    }
+
+    #[test]
+    fn dec_special_graphics_draws_lines() {
+        let set = Charset::DecSpecialGraphics;
+        let mapped: String = "lqwqk\nx x x\ntqnqu\nmqvqj"
+            .chars()
+            .map(|c| set.map(c))
+            .collect();
+        assert_eq!(mapped, "┌─┬─┐\n│ │ │\n├─┼─┤\n└─┴─┘");
+        assert_eq!(set.map('`'), '◆');
+        assert_eq!(set.map('a'), '▒');
+        assert_eq!(set.map('_'), '\u{a0}');
+        assert_eq!(set.map('~'), '·');
+        // only 0x5f..=0x7e is replaced
+        assert_eq!(set.map('A'), 'A');
+        assert_eq!(set.map('^'), '^');
+    }
+
+    #[test]
+    fn national_sets_replace_their_positions() {
+        assert_eq!(Charset::Uk.map('#'), '£');
+        assert_eq!(Charset::German.map('['), 'Ä');
+        assert_eq!(Charset::German.map('~'), 'ß');
+        assert_eq!(Charset::German.map('a'), 'a');
+        assert_eq!(Charset::Ascii.map('#'), '#');
+    }
+
+    #[test]
+    fn locking_shifts_switch_until_shifted_back() {
+        let mut charsets = Charsets::new();
+        charsets.esc(Some(b')'), b'0');
+        assert_eq!(map(&mut charsets, "qq"), "qq");
+
+        charsets.shift(true);
+        assert_eq!(map(&mut charsets, "qq"), "──");
+
+        charsets.shift(false);
+        assert_eq!(map(&mut charsets, "qq"), "qq");
+
+        charsets.esc(Some(b'*'), b'A');
+        charsets.esc(None, b'n');
+        assert_eq!(map(&mut charsets, "##"), "££");
+    }
+
+    #[test]
+    fn single_shifts_last_one_character() {
+        let mut charsets = Charsets::new();
+        charsets.esc(Some(b'*'), b'0');
+        charsets.esc(Some(b'+'), b'A');
+
+        charsets.esc(None, b'N');
+        assert_eq!(map(&mut charsets, "qq"), "─q");
+
+        charsets.esc(None, b'O');
+        assert_eq!(map(&mut charsets, "##"), "£#");
+    }
+
+    #[test]
+    fn unknown_designations_are_ignored() {
+        let mut charsets = Charsets::new();
+        charsets.esc(Some(b'('), b'0');
+        charsets.esc(Some(b'('), b'!');
+        assert_eq!(map(&mut charsets, "q"), "─");
+    }
+}
@@ -1,35 +1,23 @@
-use std::collections::VecDeque;
-
-use egui::{Color32, FontId, TextFormat, text::LayoutJob};
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
-    Config,
-    kind::{TerminalKind, style::StyleState},
+    Config, TerminalSize,
+    kind::{
+        TerminalKind,
+        line::{self, Line, Pen, RowPosition},
+        link::LinkTable,
+        modes::Modes,
+        scrollback::Scrollback,
+        sequence::Sequence,
+        shared::{self, Control, Shared},
+        style::{StyleState, StyleTable},
+        title::Title,
+    },
 };
 
 #[derive(Default)]
 struct Buffer {
-    lines: VecDeque<Line>,
-}
-
-#[derive(Default)]
-struct Line {
-    text: String,
-    sections: Vec<Section>,
-}
-impl Line {
-    fn insert(&mut self, c: char, cursor: &mut CursorPosition, fmt: TextFormat) {
-        self.text.push(c);
-        self.sections.push(Section {
-            fmt,
-            size: c.len_utf8(),
-        });
-    }
-}
-
-struct Section {
-    fmt: TextFormat,
-    size: usize,
+    lines: Scrollback,
 }
 
 #[derive(Default)]
@@ -37,7 +25,12 @@ struct CursorPosition {
     line: usize,
     column: usize,
     line_text_index: usize,
-    line_fmt_index: usize,
+    /// Cells taken up by the cursor's line, so writing at its end doesn't
+    /// have to measure it.
+    line_width: usize,
+    /// Something was written into the last column, the cursor stays on it
+    /// until the next character wraps (or, without DECAWM, overwrites it).
+    pending_wrap: bool,
 }
 
 impl CursorPosition {
@@ -46,11 +39,16 @@ impl CursorPosition {
             line: 1,
             column: 1,
             line_text_index: 0,
-            line_fmt_index: 0,
+            line_width: 0,
+            pending_wrap: false,
         }
     }
 }
 
+/// A terminal that keeps a cursor it can move around its lines.
+///
+/// Like other terminals, a bare LF only moves down and keeps the column
+/// unless LNM (`ESC [ 20 h`) is set, so programs have to send CR LF.
 pub struct Full {
     buffer: Buffer,
 
@@ -58,11 +56,17 @@ pub struct Full {
 
     show_cusror: bool,
 
+    size: TerminalSize,
+
     style: StyleState,
+    /// The style of cells skipped over by tabs and cursor movement.
+    blank: StyleState,
+    styles: StyleTable,
+    shared: Shared,
 }
 
 impl Full {
-    fn csi(&mut self, csi: ansi::KnownCSI<'_>, cfg: &Config) {
+    fn csi(&mut self, csi: ansi::KnownCSI<'_>, _: &Config) {
         match csi {
             ansi::KnownCSI::SelectGraphicRendition(gr) => {
                 for sg in gr {
@@ -70,15 +74,15 @@ impl Full {
                 }
             }
             ansi::KnownCSI::EraseDisplay => self.clear(),
-            ansi::KnownCSI::CursorRight(count) => {}
-            ansi::KnownCSI::CursorDown(count) => {}
-            ansi::KnownCSI::CursorHorizontalAbsolute(h) => {}
-            ansi::KnownCSI::CursorLeft(count) => {}
+            ansi::KnownCSI::CursorRight(_) => {}
+            ansi::KnownCSI::CursorDown(_) => {}
+            ansi::KnownCSI::CursorHorizontalAbsolute(_) => {}
+            ansi::KnownCSI::CursorLeft(_) => {}
             ansi::KnownCSI::CursorUp(_) => todo!(),
             ansi::KnownCSI::CursorNextLine(_) => todo!(),
             ansi::KnownCSI::CursorPreviousLine(_) => todo!(),
-            ansi::KnownCSI::CursorTo { row, col } => todo!(),
-            ansi::KnownCSI::HorizontalVerticalPosition { row, col } => todo!(),
+            ansi::KnownCSI::CursorTo { .. } => todo!(),
+            ansi::KnownCSI::HorizontalVerticalPosition { .. } => todo!(),
             ansi::KnownCSI::CursorPosition => todo!(),
             ansi::KnownCSI::EraseFromCursor => todo!(),
             ansi::KnownCSI::EraseToCursor => todo!(),
@@ -87,8 +91,8 @@ impl Full {
             ansi::KnownCSI::EraseFromCursorToEndOfLine => todo!(),
             ansi::KnownCSI::EraseStartOfLineToCursor => todo!(),
             ansi::KnownCSI::EraseLine => todo!(),
-            ansi::KnownCSI::ScrollUp(count) => todo!(),
-            ansi::KnownCSI::ScrollDown(count) => todo!(),
+            ansi::KnownCSI::ScrollUp(_) => todo!(),
+            ansi::KnownCSI::ScrollDown(_) => todo!(),
             ansi::KnownCSI::AuxPortOn => todo!(),
             ansi::KnownCSI::AuxPortOff => todo!(),
             ansi::KnownCSI::DeviceStatusReport => todo!(),
@@ -96,57 +100,151 @@ impl Full {
             ansi::KnownCSI::RestoreCurrentCursorPosition => todo!(),
             ansi::KnownCSI::ShowCursor => todo!(),
             ansi::KnownCSI::HideCursor => todo!(),
-            ansi::KnownCSI::EnableFocusReporting => todo!(),
-            ansi::KnownCSI::DisableFocusReporting => todo!(),
-            ansi::KnownCSI::EnableBracketPastingMode => todo!(),
-            ansi::KnownCSI::DisableBracketPastingMode => todo!(),
+            ansi::KnownCSI::EnableFocusReporting => self.shared.modes.focus_reporting = true,
+            ansi::KnownCSI::DisableFocusReporting => self.shared.modes.focus_reporting = false,
+            ansi::KnownCSI::EnableBracketPastingMode => self.shared.modes.bracketed_paste = true,
+            ansi::KnownCSI::DisableBracketPastingMode => self.shared.modes.bracketed_paste = false,
             ansi::KnownCSI::RestoreScreen => todo!(),
             ansi::KnownCSI::SaveScreen => todo!(),
             ansi::KnownCSI::EnableAlternativeBuffer => todo!(),
             ansi::KnownCSI::DisableAlternativeBuffer => todo!(),
-            ansi::KnownCSI::ScreenMode(screen_mode) => todo!(),
-            ansi::KnownCSI::ResetScreenMode(screen_mode) => todo!(),
-            ansi::KnownCSI::SetScrollingRegion { top, bottom } => todo!(),
+            // modes are set from the raw sequence in `sequence`
+            ansi::KnownCSI::ScreenMode(_) | ansi::KnownCSI::ResetScreenMode(_) => {}
+            ansi::KnownCSI::SetScrollingRegion { .. } => todo!(),
             ansi::KnownCSI::DeleteLines(_) => todo!(),
             ansi::KnownCSI::InsertLines(_) => todo!(),
             ansi::KnownCSI::CursorLineAbsolute(_) => todo!(),
-            ansi::KnownCSI::ReportedCursorPosition { row, col } => todo!(),
+            ansi::KnownCSI::ReportedCursorPosition { .. } => todo!(),
             ansi::KnownCSI::ReportCursorPosition => todo!(),
             _ => {}
         }
     }
 
     fn encounter_char(&mut self, c: char, cfg: &Config) {
-        let format = self.style.format(cfg);
-        if self.cursor.column > cfg.max_columns && c != '\n' {
-            self.insert_at_cursor('\n', format.clone());
+        let pen = Pen {
+            style: self.style.id(&mut self.styles, cfg),
+            link: self.shared.link,
+            blank: self.blank.id(&mut self.styles, cfg),
+        };
+        // with a wrap pending the cursor is on the last cluster, not after it
+        let column = if self.cursor.pending_wrap {
+            self.size.columns
+        } else {
+            self.cursor.column - 1
+        };
+        let line_width = self.cursor.line_width;
+        let mut attached = None;
+        self.buffer.lines.edit(self.cursor.line - 1, |line| {
+            attached = line.attach(column, line_width, c, pen, cfg.emoji_width);
+        });
+        if let Some((end, grown)) = attached {
+            self.cursor.line_width += grown;
+            if !self.cursor.pending_wrap {
+                self.cursor.column += grown;
+                self.cursor.line_text_index = end;
+            }
+            return;
+        }
+
+        use unicode_width::UnicodeWidthChar;
+        let width = c.width().unwrap_or_default();
+        if self.cursor.pending_wrap || self.cursor.column - 1 + width > self.size.columns {
+            if !self.shared.modes.autowrap {
+                self.move_to_column(self.size.columns.saturating_sub(width) + 1, cfg);
+            } else if self.cursor.pending_wrap || self.cursor.column > 1 {
+                self.new_line(true, cfg);
+            }
         }
-        self.insert_at_cursor(c, format);
+        self.insert_at_cursor(c, pen, cfg);
     }
 
-    fn insert_at_cursor(&mut self, c: char, fmt: TextFormat) {
+    fn insert_at_cursor(&mut self, c: char, pen: Pen, cfg: &Config) {
         use unicode_width::UnicodeWidthChar;
+        let column = self.cursor.column - 1;
+        let columns = self.size.columns;
+        let insert = self.shared.modes.insert;
+        let mut written = (0, self.cursor.line_width);
+        self.buffer.lines.edit(self.cursor.line - 1, |line| {
+            let (_, width) = written;
+            if insert && column < width {
+                written = line.insert(column, width, c, pen, cfg.emoji_width);
+                if written.1 > columns {
+                    line.truncate(columns, cfg.emoji_width);
+                    written.1 = line.width(cfg.emoji_width);
+                }
+            } else {
+                written = line.put(column, width, c, pen, cfg.emoji_width);
+            }
+        });
+        let (end, line_width) = written;
+        self.cursor.line_width = line_width;
         let width = c.width().unwrap_or_default();
-        self.cursor.column += width;
-        // self.cursor.line_text_index += c.len_utf8();
-        if c == '\n' {
-            self.cursor.line += 1;
-            self.cursor.column = 1;
-            self.cursor.line_text_index = 0;
-            self.buffer.lines.push_back(Line::default());
+        if column + width >= columns {
+            self.cursor.line_text_index = end - c.len_utf8();
+            self.cursor.pending_wrap = true;
         } else {
-            self.buffer.lines[self.cursor.line - 1].insert(c, &mut self.cursor, fmt);
+            self.cursor.column += width;
+            self.cursor.line_text_index = end;
+        }
+    }
+
+    fn control(&mut self, c0: u8, cfg: &Config) {
+        match self.shared.control(c0, cfg) {
+            Some(Control::LineFeed) => {
+                let column = self.cursor.column;
+                self.new_line(false, cfg);
+                if !self.shared.modes.newline {
+                    self.move_to_column(column, cfg);
+                }
+            }
+            Some(Control::CarriageReturn) => self.move_to_column(1, cfg),
+            Some(Control::Backspace) => {
+                self.move_to_column(self.cursor.column.saturating_sub(1), cfg);
+            }
+            Some(Control::Tab) => {
+                self.move_to_column(shared::next_tab_stop(self.cursor.column), cfg);
+            }
+            Some(Control::Picture(c)) => self.encounter_char(c, cfg),
+            None => {}
         }
     }
+
+    /// Moves the cursor along its row without writing anything.
+    fn move_to_column(&mut self, column: usize, cfg: &Config) {
+        let column = column.clamp(1, self.size.columns.max(1));
+        let line = &self.buffer.lines[self.cursor.line - 1];
+        self.cursor.column = column;
+        self.cursor.pending_wrap = false;
+        self.cursor.line_text_index = line
+            .byte_at_column(column - 1, cfg.emoji_width)
+            .unwrap_or(line.text.len());
+    }
+
+    fn new_line(&mut self, wrapped: bool, cfg: &Config) {
+        self.buffer
+            .lines
+            .edit(self.cursor.line - 1, |line| line.wrapped = wrapped);
+        self.cursor.line += 1;
+        self.cursor.column = 1;
+        self.cursor.line_text_index = 0;
+        self.cursor.line_width = 0;
+        self.cursor.pending_wrap = false;
+        let evicted = self.buffer.lines.push_back(Line::default(), cfg.scrollback);
+        self.cursor.line = self.cursor.line.saturating_sub(evicted).max(1);
+    }
 }
 
 impl TerminalKind for Full {
-    fn new(_: &crate::Config) -> Self {
+    fn new(cfg: &crate::Config) -> Self {
         let mut me = Self {
             buffer: Buffer::default(),
             show_cusror: true,
+            size: TerminalSize::from_config(cfg),
             cursor: CursorPosition::new(),
             style: StyleState::new(),
+            blank: StyleState::new(),
+            styles: StyleTable::new(),
+            shared: Shared::new(),
         };
         me.clear();
         me
@@ -154,93 +252,88 @@ impl TerminalKind for Full {
 
     fn march(&mut self, out: ansi::Out<'_>, cfg: &Config) {
         match out {
-            ansi::Out::Data(c) => self.encounter_char(c, cfg),
+            ansi::Out::Data(c) if c.is_ascii_control() => self.control(c as u8, cfg),
+            ansi::Out::Data(c) => {
+                let c = self.shared.charsets.map(c);
+                self.encounter_char(c, cfg);
+            }
             ansi::Out::SP => self.encounter_char(' ', cfg),
             ansi::Out::CSI(csi) => self.csi(csi.parse(), cfg),
-            ansi::Out::C0(c0) => self.encounter_char(c0 as u8 as char, cfg),
+            ansi::Out::C0(c0) => self.control(c0 as u8, cfg),
             _ => {}
         }
     }
 
-    fn layout(&mut self, cfg: &crate::Config, ctx: &egui::Context) -> LayoutJob {
-        let mut layout = LayoutJob::default();
+    fn sequence(&mut self, seq: Sequence<'_>, _: &Config) {
+        self.shared.sequence(seq);
+    }
 
-        let slow_enabled = cfg.slow_blink_time_seconds != 0.0;
-        let fast_enabled = cfg.fast_blink_time_seconds != 0.0;
+    fn modes(&self) -> &Modes {
+        &self.shared.modes
+    }
 
-        let time = ctx.input(|i| i.time);
+    fn lines(&self) -> &Scrollback {
+        &self.buffer.lines
+    }
 
-        let slow_rem = (time % cfg.slow_blink_time_seconds as f64) as f32;
-        let fast_rem = (time % cfg.fast_blink_time_seconds as f64) as f32;
+    fn scrollback_mut(&mut self) -> &mut Scrollback {
+        &mut self.buffer.lines
+    }
 
-        let slow_swap = slow_enabled && slow_rem > cfg.slow_blink_time_seconds / 2.0;
-        let fast_swap = fast_enabled && fast_rem > cfg.fast_blink_time_seconds / 2.0;
+    fn styles(&self) -> &StyleTable {
+        &self.styles
+    }
 
-        let mut slow = false;
-        let mut fast = false;
+    fn links(&self) -> &LinkTable {
+        &self.shared.links
+    }
 
-        for (line, contents) in self.buffer.lines.iter().enumerate() {
-            let line = line + 1;
-            let mut offset = 0;
-            for section in &contents.sections {
-                let end = offset + section.size;
-                let mut format = section.fmt.clone();
-                if format.line_height == Some(0.0) && slow_swap {
-                    slow = true;
-                    if slow_swap {
-                        std::mem::swap(&mut format.background, &mut format.color);
-                    }
-                } else if format.line_height == Some(1.0) {
-                    fast = true;
-                    if fast_swap {
-                        std::mem::swap(&mut format.background, &mut format.color);
-                    }
-                }
+    fn cursor(&self) -> Option<RowPosition> {
+        self.show_cusror.then_some(RowPosition {
+            row: self.cursor.line - 1,
+            byte: self.cursor.line_text_index,
+        })
+    }
 
-                format.line_height = None;
-                let spacing = if self.style.proportional {
-                    0.0
-                } else {
-                    format.extra_letter_spacing
-                };
-                layout.append(&contents.text[offset..end], spacing, format);
-                offset = end;
-            }
-            if line == self.cursor.line && self.cursor.line_text_index == offset{
-                layout.append(
-                    " ",
-                    0.0,
-                    TextFormat {
-                        font_id: FontId::monospace(cfg.font_size),
-                        color: Color32::TRANSPARENT,
-                        background: cfg.fg_default,
-                        ..Default::default()
-                    },
-                );
-            }
-            if line != self.buffer.lines.len() {
-                layout.append(
-                    "\n",
-                    0.0,
-                    TextFormat::simple(FontId::monospace(cfg.font_size), Color32::TRANSPARENT),
-                );
-            }
-        }
+    fn title(&self) -> &Title {
+        &self.shared.title
+    }
 
-        if slow {
-            let half = cfg.slow_blink_time_seconds / 2.0;
-            ctx.request_repaint_after_secs(half - (time % half as f64) as f32);
+    fn resize(&mut self, size: TerminalSize, cfg: &Config) {
+        let reflow = self.size.columns != size.columns;
+        self.size = size;
+        if !reflow {
+            return;
         }
-        if fast {
-            let half = cfg.fast_blink_time_seconds / 2.0;
-            ctx.request_repaint_after_secs(half - (time % half as f64) as f32);
+        let mut byte = self.cursor.line_text_index;
+        if self.cursor.pending_wrap {
+            // the wrap is resolved by moving the cursor after the last cluster
+            let text = &self.buffer.lines[self.cursor.line - 1].text;
+            byte += text[byte..].graphemes(true).next().map_or(0, str::len);
         }
-        layout
+        let mut cursor = RowPosition {
+            row: self.cursor.line - 1,
+            byte,
+        };
+        self.buffer
+            .lines
+            .edit_all(|lines| line::reflow(lines, size.columns, cfg.emoji_width, &mut cursor));
+        // narrower rows can take up more lines than the scrollback allows
+        let evicted = self.buffer.lines.trim(cfg.scrollback);
+        cursor = match cursor.row.checked_sub(evicted) {
+            Some(row) => RowPosition { row, ..cursor },
+            None => RowPosition::default(),
+        };
+        let row = &self.buffer.lines[cursor.row];
+        self.cursor.line = cursor.row + 1;
+        self.cursor.line_text_index = cursor.byte;
+        self.cursor.pending_wrap = false;
+        self.cursor.line_width = row.width(cfg.emoji_width);
+        self.cursor.column = line::text_width(&row.text[..cursor.byte], cfg.emoji_width) + 1;
     }
 
     fn clear(&mut self) {
         self.cursor = CursorPosition::new();
         self.buffer.lines.clear();
-        self.buffer.lines.push_back(Line::default());
     }
 }
@@ -0,0 +1,668 @@
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    EmojiWidth,
+    kind::{link::LinkId, style::StyleId},
+};
+
+#[derive(Debug, Default, Clone)]
+pub struct Line {
+    pub(crate) text: String,
+    pub(crate) sections: Vec<Section>,
+    /// Soft-wrapped, the logical line continues on the next row.
+    pub(crate) wrapped: bool,
+    /// Changes whenever the contents change, `0` for empty lines.
+    pub(crate) revision: u64,
+}
+
+fn next_revision() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// What characters are written into a line with.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Pen {
+    pub(crate) style: StyleId,
+    pub(crate) link: Option<LinkId>,
+    /// The style of the spaces a line is padded with up to a character
+    /// written past its end.
+    pub(crate) blank: StyleId,
+}
+
+/// A run of bytes sharing one style and hyperlink.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Section {
+    pub(crate) style: StyleId,
+    pub(crate) link: Option<LinkId>,
+    pub(crate) size: usize,
+}
+
+impl Section {
+    fn continues(&self, other: &Section) -> bool {
+        self.style == other.style && self.link == other.link
+    }
+}
+
+impl Line {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_wrapped(&self) -> bool {
+        self.wrapped
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// The number of runs of differently styled text.
+    pub fn style_runs(&self) -> usize {
+        self.sections.len()
+    }
+
+    /// Bytes allocated for the text and style runs of the line.
+    pub fn heap_size(&self) -> usize {
+        self.text.capacity() + self.sections.capacity() * std::mem::size_of::<Section>()
+    }
+
+    /// The number of cells the line takes up.
+    pub fn width(&self, emoji: EmojiWidth) -> usize {
+        text_width(&self.text, emoji)
+    }
+
+    pub(crate) fn push(&mut self, c: char, style: StyleId, link: Option<LinkId>) {
+        self.revision = next_revision();
+        self.text.push(c);
+        let section = Section {
+            style,
+            link,
+            size: c.len_utf8(),
+        };
+        match self.sections.last_mut() {
+            Some(last) if last.continues(&section) => last.size += section.size,
+            _ => self.sections.push(section),
+        }
+    }
+
+    /// Writes `c` over the grapheme clusters covering the cells it takes up
+    /// from cell `column` of the line, which is `width` cells wide, padding
+    /// it with blanks if it's shorter. Cells of overwritten wide clusters
+    /// which `c` doesn't cover are left blank, so the rest of the line stays
+    /// where it is. Returns the byte index after `c` and the new width.
+    ///
+    /// Only writing inside of the line has to measure it.
+    pub(crate) fn put(
+        &mut self,
+        column: usize,
+        width: usize,
+        c: char,
+        pen: Pen,
+        emoji: EmojiWidth,
+    ) -> (usize, usize) {
+        if column >= width {
+            return self.push_at(column, width, c, pen);
+        }
+        let cells = c.width().unwrap_or_default().max(1);
+        let mut start = None;
+        let mut end = self.text.len();
+        // blank cells before and after `c` left over from wide clusters
+        let (mut before, mut after) = (0, 0);
+        let mut cell = 0;
+        for (i, cluster) in self.text.grapheme_indices(true) {
+            let next = cell + cluster_width(cluster, emoji);
+            if start.is_none() && next > column {
+                start = Some(i);
+                before = column - cell;
+            }
+            if start.is_some() && next >= column + cells {
+                end = i + cluster.len();
+                after = next - column - cells;
+                break;
+            }
+            cell = next;
+        }
+        let Some(start) = start else {
+            return self.push_at(column, width, c, pen);
+        };
+
+        let mut rest = self.split_off(start);
+        let rest = rest.split_off(end - start);
+        for _ in 0..before {
+            self.push(' ', pen.blank, None);
+        }
+        self.push(c, pen.style, pen.link);
+        let at = self.text.len();
+        for _ in 0..after {
+            self.push(' ', pen.blank, None);
+        }
+        self.append(rest);
+        (at, self.width(emoji))
+    }
+
+    /// Writes `c` before the grapheme cluster covering cell `column`, moving
+    /// the rest of the line to the right. Returns the same as [`Line::put`].
+    pub(crate) fn insert(
+        &mut self,
+        column: usize,
+        width: usize,
+        c: char,
+        pen: Pen,
+        emoji: EmojiWidth,
+    ) -> (usize, usize) {
+        if column >= width {
+            return self.push_at(column, width, c, pen);
+        }
+        let Some(at) = self.byte_at_column(column, emoji) else {
+            return self.push_at(column, width, c, pen);
+        };
+        let rest = self.split_off(at);
+        self.push(c, pen.style, pen.link);
+        let end = self.text.len();
+        self.append(rest);
+        (end, self.width(emoji))
+    }
+
+    /// Appends `c` at cell `column` at or past the end of the line.
+    fn push_at(&mut self, column: usize, width: usize, c: char, pen: Pen) -> (usize, usize) {
+        for _ in width..column {
+            self.push(' ', pen.blank, None);
+        }
+        self.push(c, pen.style, pen.link);
+        (
+            self.text.len(),
+            column.max(width) + c.width().unwrap_or_default(),
+        )
+    }
+
+    /// Drops the grapheme clusters which don't fit into `columns` cells.
+    pub(crate) fn truncate(&mut self, columns: usize, emoji: EmojiWidth) {
+        if let Some(at) = self.wrap_point(columns, emoji) {
+            let wrapped = self.wrapped;
+            self.split_off(at);
+            self.wrapped = wrapped;
+        }
+    }
+
+    /// Adds `c` to the grapheme cluster ending right before cell `column` of
+    /// the line, which is `width` cells wide, if it continues it, like a
+    /// combining mark or the rest of an emoji sequence. Returns the byte index
+    /// after `c` and how many cells the cluster grew by.
+    pub(crate) fn attach(
+        &mut self,
+        column: usize,
+        width: usize,
+        c: char,
+        pen: Pen,
+        emoji: EmojiWidth,
+    ) -> Option<(usize, usize)> {
+        if c.is_ascii() || column == 0 || column > width {
+            return None;
+        }
+        let (start, cluster) = if column == width {
+            // writing at the end, only the last cluster matters
+            self.text.grapheme_indices(true).next_back()?
+        } else {
+            let mut cells = 0;
+            let mut previous = None;
+            for (i, cluster) in self.text.grapheme_indices(true) {
+                if cells >= column {
+                    break;
+                }
+                cells += cluster_width(cluster, emoji);
+                previous = Some((i, cluster));
+            }
+            previous.filter(|_| cells == column)?
+        };
+        let at = start + cluster.len();
+
+        let mut extended = String::with_capacity(cluster.len() + c.len_utf8());
+        extended.push_str(cluster);
+        extended.push(c);
+        if extended.graphemes(true).nth(1).is_some() {
+            return None;
+        }
+        let grown = cluster_width(&extended, emoji).saturating_sub(cluster_width(cluster, emoji));
+
+        if at == self.text.len() {
+            self.push(c, pen.style, pen.link);
+        } else {
+            let rest = self.split_off(at);
+            self.push(c, pen.style, pen.link);
+            self.append(rest);
+        }
+        Some((at + c.len_utf8(), grown))
+    }
+
+    /// The hyperlink of the character at byte `at`.
+    pub fn link_at(&self, at: usize) -> Option<LinkId> {
+        let mut offset = 0;
+        for section in &self.sections {
+            offset += section.size;
+            if at < offset {
+                return section.link;
+            }
+        }
+        None
+    }
+
+    /// Byte ranges of the text linking to `link`.
+    pub fn link_ranges(&self, link: LinkId) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut offset = 0;
+        for section in &self.sections {
+            let end = offset + section.size;
+            if section.link == Some(link) {
+                match ranges.last_mut() {
+                    Some(last) if last.end == offset => last.end = end,
+                    _ => ranges.push(offset..end),
+                }
+            }
+            offset = end;
+        }
+        ranges
+    }
+
+    /// The byte index of the grapheme cluster covering cell `column`.
+    pub fn byte_at_column(&self, column: usize, emoji: EmojiWidth) -> Option<usize> {
+        let mut width = 0;
+        for (i, cluster) in self.text.grapheme_indices(true) {
+            width += cluster_width(cluster, emoji);
+            if width > column {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    fn append(&mut self, other: Line) {
+        self.revision = next_revision();
+        self.text.push_str(&other.text);
+        let mut sections = other.sections.into_iter();
+        if let Some(first) = sections.next() {
+            match self.sections.last_mut() {
+                Some(last) if last.continues(&first) => last.size += first.size,
+                _ => self.sections.push(first),
+            }
+        }
+        self.sections.extend(sections);
+        self.wrapped = other.wrapped;
+    }
+
+    /// Splits the line at byte `at`, leaving `[0, at)` in `self`.
+    pub(crate) fn split_off(&mut self, at: usize) -> Line {
+        self.revision = next_revision();
+        let text = self.text.split_off(at);
+        let mut offset = 0;
+        let mut split = self.sections.len();
+        let mut tail = Vec::new();
+        for (i, section) in self.sections.iter_mut().enumerate() {
+            let end = offset + section.size;
+            if end > at {
+                if offset < at {
+                    tail.push(Section {
+                        size: end - at,
+                        ..*section
+                    });
+                    section.size = at - offset;
+                    split = i + 1;
+                } else {
+                    split = i;
+                }
+                break;
+            }
+            offset = end;
+        }
+        tail.extend(self.sections.drain(split..));
+        Line {
+            text,
+            sections: tail,
+            wrapped: std::mem::take(&mut self.wrapped),
+            revision: next_revision(),
+        }
+    }
+
+    /// The byte index after the grapheme cluster that fills up `columns`
+    /// cells.
+    fn wrap_point(&self, columns: usize, emoji: EmojiWidth) -> Option<usize> {
+        let mut width = 0;
+        for (i, cluster) in self.text.grapheme_indices(true) {
+            width += cluster_width(cluster, emoji);
+            if width > columns {
+                // a single cluster wider than the line still has to go somewhere
+                return Some(if i == 0 { cluster.len() } else { i });
+            }
+        }
+        None
+    }
+}
+
+/// The symbol in the Control Pictures block standing for C0 control `c0` or
+/// DEL.
+pub(crate) fn control_picture(c0: u8) -> char {
+    match c0 {
+        0x7f => '\u{2421}',
+        _ => char::from_u32(0x2400 + c0 as u32).unwrap_or(char::REPLACEMENT_CHARACTER),
+    }
+}
+
+/// The cells a grapheme cluster takes up, the width of its first character
+/// unless it's an emoji sequence and `emoji` makes those wide.
+pub(crate) fn cluster_width(cluster: &str, emoji: EmojiWidth) -> usize {
+    let mut chars = cluster.chars();
+    let Some(first) = chars.next() else {
+        return 0;
+    };
+    let width = first.width().unwrap_or_default();
+    if emoji == EmojiWidth::Wide
+        && chars.any(|c| {
+            matches!(c,
+                // emoji presentation selector and zero width joiner
+                '\u{fe0f}' | '\u{200d}'
+                // skin tone modifiers
+                | '\u{1f3fb}'..='\u{1f3ff}'
+                // the second half of a flag
+                | '\u{1f1e6}'..='\u{1f1ff}')
+        })
+    {
+        return 2;
+    }
+    width
+}
+
+pub(crate) fn text_width(text: &str, emoji: EmojiWidth) -> usize {
+    text.graphemes(true)
+        .map(|cluster| cluster_width(cluster, emoji))
+        .sum()
+}
+
+/// Position of a character inside of a list of rows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RowPosition {
+    pub row: usize,
+    pub byte: usize,
+}
+
+/// Re-wraps `rows` to `columns`, joining soft-wrapped rows into their
+/// logical lines first. `cursor` is moved along so it stays on the same
+/// logical character.
+pub(crate) fn reflow(
+    rows: &mut VecDeque<Line>,
+    columns: usize,
+    emoji: EmojiWidth,
+    cursor: &mut RowPosition,
+) {
+    let columns = columns.max(1);
+    let old = std::mem::take(rows);
+    let count = old.len();
+    // `cursor` is moved while splitting, so look for the row it started on
+    let start = *cursor;
+
+    let mut logical = Line::default();
+    let mut logical_cursor = None;
+    for (i, row) in old.into_iter().enumerate() {
+        if i == start.row {
+            logical_cursor = Some(logical.text.len() + start.byte);
+        }
+        let wrapped = row.wrapped;
+        logical.append(row);
+        if !wrapped || i + 1 == count {
+            logical.wrapped = false;
+            let logical_cursor = logical_cursor.take();
+            split_rows(
+                std::mem::take(&mut logical),
+                columns,
+                emoji,
+                rows,
+                logical_cursor,
+                cursor,
+            );
+        }
+    }
+    if rows.is_empty() {
+        rows.push_back(Line::default());
+        *cursor = RowPosition::default();
+    }
+}
+
+fn split_rows(
+    mut line: Line,
+    columns: usize,
+    emoji: EmojiWidth,
+    rows: &mut VecDeque<Line>,
+    mut logical_cursor: Option<usize>,
+    cursor: &mut RowPosition,
+) {
+    while let Some(at) = line.wrap_point(columns, emoji) {
+        let rest = line.split_off(at);
+        line.wrapped = true;
+        if let Some(byte) = logical_cursor
+            && byte < at
+        {
+            *cursor = RowPosition {
+                row: rows.len(),
+                byte,
+            };
+            logical_cursor = None;
+        }
+        logical_cursor = logical_cursor.map(|byte| byte - at);
+        rows.push_back(line);
+        line = rest;
+    }
+    if let Some(byte) = logical_cursor {
+        *cursor = RowPosition {
+            row: rows.len(),
+            byte: byte.min(line.text.len()),
+        };
+    }
+    rows.push_back(line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(rows: &[(&str, bool)]) -> VecDeque<Line> {
+        rows.iter()
+            .map(|&(text, wrapped)| {
+                let mut line = Line::default();
+                for c in text.chars() {
+                    line.push(c, StyleId(0), None);
+                }
+                line.wrapped = wrapped;
+                line
+            })
+            .collect()
+    }
+
+    fn texts(rows: &VecDeque<Line>) -> Vec<(&str, bool)> {
+        rows.iter().map(|row| (row.text(), row.wrapped)).collect()
+    }
+
+    #[test]
+    fn reflow_moves_cursor_along() {
+        let mut lines = rows(&[("hello world", false), ("next", false)]);
+        let mut cursor = RowPosition { row: 0, byte: 7 };
+        reflow(&mut lines, 5, EmojiWidth::Wide, &mut cursor);
+        assert_eq!(
+            texts(&lines),
+            [
+                ("hello", true),
+                (" worl", true),
+                ("d", false),
+                ("next", false)
+            ]
+        );
+        assert_eq!(cursor, RowPosition { row: 1, byte: 2 });
+
+        let mut cursor = RowPosition { row: 3, byte: 4 };
+        reflow(&mut lines, 80, EmojiWidth::Wide, &mut cursor);
+        assert_eq!(texts(&lines), [("hello world", false), ("next", false)]);
+        assert_eq!(cursor, RowPosition { row: 1, byte: 4 });
+    }
+
+    #[test]
+    fn reflow_keeps_cursor_at_end_of_line() {
+        let mut lines = rows(&[("hello world", false)]);
+        let mut cursor = RowPosition { row: 0, byte: 11 };
+        reflow(&mut lines, 5, EmojiWidth::Wide, &mut cursor);
+        assert_eq!(cursor, RowPosition { row: 2, byte: 1 });
+    }
+
+    #[test]
+    fn reflow_wraps_before_wide_characters() {
+        let mut lines = rows(&[("a漢字b", false)]);
+        let mut cursor = RowPosition { row: 0, byte: 4 };
+        reflow(&mut lines, 4, EmojiWidth::Wide, &mut cursor);
+        assert_eq!(texts(&lines), [("a漢", true), ("字b", false)]);
+        assert_eq!(cursor, RowPosition { row: 1, byte: 0 });
+    }
+
+    #[test]
+    fn reflow_gives_too_wide_characters_their_own_row() {
+        let mut lines = rows(&[("a漢b", false)]);
+        let mut cursor = RowPosition::default();
+        reflow(&mut lines, 1, EmojiWidth::Wide, &mut cursor);
+        assert_eq!(texts(&lines), [("a", true), ("漢", true), ("b", false)]);
+    }
+
+    #[test]
+    fn reflow_rejoins_soft_wrapped_rows() {
+        let mut lines = rows(&[("hel", true), ("lo wo", true), ("rld", false), ("x", false)]);
+        let mut cursor = RowPosition { row: 2, byte: 1 };
+        reflow(&mut lines, 20, EmojiWidth::Wide, &mut cursor);
+        assert_eq!(texts(&lines), [("hello world", false), ("x", false)]);
+        assert_eq!(cursor, RowPosition { row: 0, byte: 9 });
+    }
+
+    #[test]
+    fn reflow_leaves_one_empty_row() {
+        let mut lines = VecDeque::new();
+        let mut cursor = RowPosition { row: 3, byte: 2 };
+        reflow(&mut lines, 10, EmojiWidth::Wide, &mut cursor);
+        assert_eq!(texts(&lines), [("", false)]);
+        assert_eq!(cursor, RowPosition::default());
+    }
+
+    const PEN: Pen = Pen {
+        style: StyleId(0),
+        link: None,
+        blank: StyleId(1),
+    };
+
+    fn line(text: &str) -> Line {
+        rows(&[(text, false)]).pop_front().unwrap()
+    }
+
+    /// Puts `c` at `column` of `text`, returning the new text, the byte after
+    /// `c` and the new width.
+    fn put(text: &str, column: usize, c: char) -> (String, usize, usize) {
+        let mut line = line(text);
+        let width = line.width(EmojiWidth::Wide);
+        let (end, width) = line.put(column, width, c, PEN, EmojiWidth::Wide);
+        assert_eq!(width, line.width(EmojiWidth::Wide));
+        (line.text, end, width)
+    }
+
+    /// Attaches `c` at `column` of `text`, returning the new text and what
+    /// `attach` returned.
+    fn attach(text: &str, column: usize, c: char) -> (String, Option<(usize, usize)>) {
+        let mut line = line(text);
+        let width = line.width(EmojiWidth::Wide);
+        let attached = line.attach(column, width, c, PEN, EmojiWidth::Wide);
+        (line.text, attached)
+    }
+
+    #[test]
+    fn put_overwrites_one_cell() {
+        assert_eq!(put("abc", 1, 'x'), ("axc".into(), 2, 3));
+        assert_eq!(put("ab", 4, 'x'), ("ab  x".into(), 5, 5));
+    }
+
+    #[test]
+    fn put_wide_over_narrow_keeps_the_rest_in_place() {
+        assert_eq!(put("abcd", 1, '中'), ("a中d".into(), 4, 4));
+        // the last cell only has room for half of it
+        assert_eq!(put("abc", 2, '中'), ("ab中".into(), 5, 4));
+    }
+
+    #[test]
+    fn put_over_part_of_wide_blanks_the_rest() {
+        assert_eq!(put("a中d", 1, 'x'), ("ax d".into(), 2, 4));
+        assert_eq!(put("a中d", 2, 'x'), ("a xd".into(), 3, 4));
+        assert_eq!(put("中文", 1, '字'), (" 字 ".into(), 4, 4));
+
+        let mut line = line("a中d");
+        line.put(1, 4, 'x', PEN, EmojiWidth::Wide);
+        let styles: Vec<_> = line.sections.iter().map(|s| (s.style, s.size)).collect();
+        assert_eq!(styles, [(StyleId(0), 2), (StyleId(1), 1), (StyleId(0), 1)]);
+    }
+
+    #[test]
+    fn put_over_a_cluster_replaces_all_of_it() {
+        assert_eq!(put("ae\u{301}b", 1, 'x'), ("axb".into(), 2, 3));
+    }
+
+    #[test]
+    fn attach_continues_clusters() {
+        assert_eq!(attach("e", 1, '\u{301}'), ("e\u{301}".into(), Some((3, 0))));
+        assert_eq!(
+            attach("e!", 1, '\u{301}'),
+            ("e\u{301}!".into(), Some((3, 0)))
+        );
+        assert_eq!(
+            attach("👨", 2, '\u{200d}'),
+            ("👨\u{200d}".into(), Some((7, 0)))
+        );
+        assert_eq!(
+            attach("👨\u{200d}", 2, '👩'),
+            ("👨\u{200d}👩".into(), Some((11, 0)))
+        );
+        // an emoji presentation selector makes a narrow symbol wide
+        assert_eq!(
+            attach("❤", 1, '\u{fe0f}'),
+            ("❤\u{fe0f}".into(), Some((6, 1)))
+        );
+    }
+
+    #[test]
+    fn attach_leaves_new_clusters_alone() {
+        assert_eq!(attach("e", 1, 'x'), ("e".into(), None));
+        assert_eq!(attach("e", 1, 'é'), ("e".into(), None));
+        assert_eq!(attach("", 0, '\u{301}'), ("".into(), None));
+        assert_eq!(attach("ab", 3, '\u{301}'), ("ab".into(), None));
+    }
+
+    #[test]
+    fn cluster_width_depends_on_emoji_width() {
+        for (cluster, wide, narrow) in [
+            ("a", 1, 1),
+            ("中", 2, 2),
+            ("e\u{301}", 1, 1),
+            ("❤\u{fe0f}", 2, 1),
+            ("👍\u{1f3fd}", 2, 2),
+            ("🏳\u{fe0f}\u{200d}🌈", 2, 1),
+            ("👨\u{200d}👩\u{200d}👧", 2, 2),
+            ("\u{301}", 0, 0),
+        ] {
+            assert_eq!(
+                cluster_width(cluster, EmojiWidth::Wide),
+                wide,
+                "{cluster:?}"
+            );
+            assert_eq!(
+                cluster_width(cluster, EmojiWidth::Narrow),
+                narrow,
+                "{cluster:?}"
+            );
+        }
+    }
+}
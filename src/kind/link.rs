@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+
+use crate::kind::sequence::Osc;
+
+/// Index of a URI in a [`LinkTable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LinkId(pub(crate) u32);
+
+/// Every distinct hyperlink target a terminal has seen through OSC 8.
+#[derive(Debug, Default)]
+pub struct LinkTable {
+    uris: Vec<String>,
+    ids: HashMap<String, LinkId>,
+}
+
+impl LinkTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, uri: &str) -> LinkId {
+        if let Some(id) = self.ids.get(uri) {
+            return *id;
+        }
+        let id = LinkId(self.uris.len() as u32);
+        self.uris.push(uri.to_owned());
+        self.ids.insert(uri.to_owned(), id);
+        id
+    }
+
+    pub fn get(&self, id: LinkId) -> &str {
+        &self.uris[id.0 as usize]
+    }
+
+    /// Handles `OSC 8 ; params ; URI ST`, returning the link text written
+    /// from now on should point to. An empty URI ends the link.
+    pub(crate) fn osc(&mut self, osc: &Osc<'_>) -> Option<LinkId> {
+        let data = osc.data_str()?;
+        let (_params, uri) = data.split_once(';')?;
+        (!uri.is_empty()).then(|| self.intern(uri))
+    }
+}
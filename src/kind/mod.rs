@@ -1,14 +1,63 @@
 use egui::text::LayoutJob;
 
-use crate::Config;
+use crate::{
+    Config, TerminalSize,
+    kind::{
+        line::RowPosition,
+        link::LinkTable,
+        modes::Modes,
+        scrollback::Scrollback,
+        sequence::Sequence,
+        style::StyleTable,
+        title::Title,
+    },
+};
 
+#[cfg(feature = "archive")]
+pub mod archive;
 pub mod basic;
+pub mod charset;
 pub mod full;
+pub mod line;
+pub mod link;
+pub mod modes;
+pub mod render;
+pub mod scrollback;
+pub mod sequence;
+mod shared;
 pub mod style;
+pub mod title;
 
 pub trait TerminalKind {
     fn new(cfg: &Config) -> Self;
     fn march(&mut self, data: ansi::Out<'_>, cfg: &Config);
-    fn layout(&mut self, cfg: &Config, ctx: &egui::Context) -> LayoutJob;
+    fn sequence(&mut self, seq: Sequence<'_>, cfg: &Config);
+    fn modes(&self) -> &Modes;
+    fn lines(&self) -> &Scrollback;
+    fn scrollback_mut(&mut self) -> &mut Scrollback;
+    /// The formats the style ids in [`TerminalKind::lines`] refer to.
+    fn styles(&self) -> &StyleTable;
+    /// The URIs the link ids in [`TerminalKind::lines`] refer to.
+    fn links(&self) -> &LinkTable;
+    fn cursor(&self) -> Option<RowPosition>;
+    fn title(&self) -> &Title;
+
+    /// The rows of [`TerminalKind::lines`] still kept in memory, soft-wrapped
+    /// rows counted one by one and archived ones left out, see
+    /// [`Scrollback::total_rows`] for those.
+    ///
+    /// Both kinds always have a row for what's written next. `Basic` only
+    /// ever writes to its last row, so a trailing LF counts as an empty row.
+    /// `Full` keeps every row from the top of its scrollback to the one its
+    /// cursor moved furthest down to.
+    fn line_count(&self) -> usize {
+        self.lines().len()
+    }
+
+    fn layout(&mut self, cfg: &Config, ctx: &egui::Context) -> LayoutJob {
+        render::layout(self.lines(), self.styles(), self.cursor(), cfg, ctx)
+    }
+
+    fn resize(&mut self, size: TerminalSize, cfg: &Config);
     fn clear(&mut self);
 }
@@ -0,0 +1,127 @@
+use crate::kind::sequence::Csi;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MouseTracking {
+    #[default]
+    Off,
+    /// `?9`, presses only.
+    X10,
+    /// `?1000`, presses and releases.
+    Normal,
+    /// `?1002`, also motion while a button is held.
+    ButtonEvent,
+    /// `?1003`, all motion.
+    AnyEvent,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEncoding {
+    #[default]
+    Default,
+    /// `?1005`
+    Utf8,
+    /// `?1006`
+    Sgr,
+}
+
+#[derive(Debug, Clone)]
+pub struct Modes {
+    /// DECCKM, cursor keys send `ESC O x` instead of `ESC [ x`.
+    pub application_cursor_keys: bool,
+    pub mouse_tracking: MouseTracking,
+    pub mouse_encoding: MouseEncoding,
+    /// `?1004`, send `ESC [ I` / `ESC [ O` when the widget gains or loses focus.
+    pub focus_reporting: bool,
+    /// `?2004`, wrap pasted text in `ESC [ 200 ~` / `ESC [ 201 ~`.
+    pub bracketed_paste: bool,
+    /// DECAWM `?7`, writing past the last column continues on the next row.
+    /// Otherwise the last cell is overwritten.
+    pub autowrap: bool,
+    /// IRM `4`, written characters push the rest of the row to the right.
+    pub insert: bool,
+    /// LNM `20`, LF, VT and FF also return to the first column and Enter
+    /// sends CR LF. Off by default, [`Basic`](crate::kind::basic::Basic) always returns.
+    pub newline: bool,
+    /// DECSCNM `?5`, the default foreground and background are swapped.
+    pub reverse_video: bool,
+}
+
+impl Default for Modes {
+    fn default() -> Self {
+        Self {
+            application_cursor_keys: false,
+            mouse_tracking: MouseTracking::Off,
+            mouse_encoding: MouseEncoding::Default,
+            focus_reporting: false,
+            bracketed_paste: false,
+            autowrap: true,
+            insert: false,
+            newline: false,
+            reverse_video: false,
+        }
+    }
+}
+
+impl Modes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn csi(&mut self, csi: &Csi<'_>) {
+        let enabled = match csi.final_byte {
+            b'h' => true,
+            b'l' => false,
+            _ => return,
+        };
+        if csi.is_private() {
+            for mode in csi.params() {
+                self.set_private(mode, enabled);
+            }
+        } else if csi.private.is_none() && csi.intermediates.is_empty() {
+            for mode in csi.params() {
+                self.set_ansi(mode, enabled);
+            }
+        }
+    }
+
+    pub fn set_ansi(&mut self, mode: u16, enabled: bool) {
+        match mode {
+            4 => self.insert = enabled,
+            20 => self.newline = enabled,
+            _ => {}
+        }
+    }
+
+    pub fn set_private(&mut self, mode: u16, enabled: bool) {
+        let tracking = |tracking| {
+            if enabled {
+                tracking
+            } else {
+                MouseTracking::Off
+            }
+        };
+        let encoding = |encoding| {
+            if enabled {
+                encoding
+            } else {
+                MouseEncoding::Default
+            }
+        };
+        match mode {
+            1 => self.application_cursor_keys = enabled,
+            5 => self.reverse_video = enabled,
+            // DECOM needs scrolling regions to be relative to, which Full
+            // doesn't have yet, so the cursor always stays relative to the
+            // whole screen
+            6 => {}
+            7 => self.autowrap = enabled,
+            9 => self.mouse_tracking = tracking(MouseTracking::X10),
+            1000 => self.mouse_tracking = tracking(MouseTracking::Normal),
+            1002 => self.mouse_tracking = tracking(MouseTracking::ButtonEvent),
+            1003 => self.mouse_tracking = tracking(MouseTracking::AnyEvent),
+            1005 => self.mouse_encoding = encoding(MouseEncoding::Utf8),
+            1006 => self.mouse_encoding = encoding(MouseEncoding::Sgr),
+            _ => {}
+        }
+    }
+}
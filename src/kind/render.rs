@@ -0,0 +1,267 @@
+use std::{collections::VecDeque, ops::Range};
+
+use egui::{
+    Color32, FontFamily, FontId, TextFormat,
+    text::{LayoutJob, LayoutSection, TextWrapping},
+};
+
+use crate::{
+    Config,
+    kind::{
+        line::{Line, RowPosition},
+        style::{StyleState, StyleTable},
+    },
+};
+
+/// Which blink speeds are currently in their swapped phase, or which ones a
+/// line makes use of.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Blink {
+    pub slow: bool,
+    pub fast: bool,
+}
+
+impl Blink {
+    pub fn now(cfg: &Config, ctx: &egui::Context) -> Self {
+        let time = ctx.input(|i| i.time);
+        let swapped = |period: f32| period != 0.0 && (time % period as f64) as f32 > period / 2.0;
+        Self {
+            slow: swapped(cfg.slow_blink_time_seconds),
+            fast: swapped(cfg.fast_blink_time_seconds),
+        }
+    }
+
+    pub fn of(line: &Line, styles: &StyleTable) -> Self {
+        let mut used = Self::default();
+        for section in &line.sections {
+            let format = styles.get(section.style);
+            used.slow |= format.line_height == Some(0.0);
+            used.fast |= format.line_height == Some(1.0);
+        }
+        used
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            slow: self.slow || other.slow,
+            fast: self.fast || other.fast,
+        }
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        Self {
+            slow: self.slow && other.slow,
+            fast: self.fast && other.fast,
+        }
+    }
+
+    /// Schedules the next repaint for the blink speeds in use.
+    pub fn request_repaint(self, cfg: &Config, ctx: &egui::Context) {
+        let time = ctx.input(|i| i.time);
+        for (used, period) in [
+            (self.slow, cfg.slow_blink_time_seconds),
+            (self.fast, cfg.fast_blink_time_seconds),
+        ] {
+            if used && period != 0.0 {
+                let half = period / 2.0;
+                ctx.request_repaint_after_secs(half - (time % half as f64) as f32);
+            }
+        }
+    }
+}
+
+/// Appends `line` to `job`, swapping the colors of blinking sections which
+/// are in their swapped phase. Returns which blink speeds the line uses.
+pub fn append_line(
+    job: &mut LayoutJob,
+    line: &Line,
+    styles: &StyleTable,
+    blink: Blink,
+) -> Blink {
+    let mut used = Blink::default();
+    let mut offset = 0;
+    for section in &line.sections {
+        let end = offset + section.size;
+        let mut format = styles.get(section.style).clone();
+        if format.line_height == Some(0.0) {
+            used.slow = true;
+            if blink.slow {
+                std::mem::swap(&mut format.background, &mut format.color);
+            }
+        } else if format.line_height == Some(1.0) {
+            used.fast = true;
+            if blink.fast {
+                std::mem::swap(&mut format.background, &mut format.color);
+            }
+        }
+        format.line_height = None;
+        let spacing = if format.font_id.family == FontFamily::Proportional {
+            0.0
+        } else {
+            format.extra_letter_spacing
+        };
+        job.append(&line.text[offset..end], spacing, format);
+        offset = end;
+    }
+    used
+}
+
+pub fn append_newline(job: &mut LayoutJob, cfg: &Config) {
+    job.append(
+        "\n",
+        0.0,
+        TextFormat::simple(FontId::monospace(cfg.font_size), Color32::TRANSPARENT),
+    );
+}
+
+/// Draws the cursor at byte `at` of `job`, as a block after the text if it
+/// is at the end or by inverting the character under it otherwise.
+pub fn append_cursor(job: &mut LayoutJob, at: usize, cfg: &Config) {
+    match job.text[at.min(job.text.len())..].chars().next() {
+        Some('\n') | None => job.append(
+            " ",
+            0.0,
+            TextFormat {
+                font_id: FontId::monospace(cfg.font_size),
+                color: Color32::TRANSPARENT,
+                background: cfg.fg_default,
+                ..Default::default()
+            },
+        ),
+        Some(c) => {
+            let cell = at..at + c.len_utf8();
+            overlay(job, std::slice::from_ref(&cell), |_, format| {
+                format.color = cfg.bg_default;
+                format.background = cfg.fg_default;
+            });
+        }
+    }
+}
+
+/// Swaps the default foreground and background colors of `format`, for
+/// DECSCNM.
+pub fn reverse_screen(format: &mut TextFormat, cfg: &Config) {
+    let (fg, bg) = StyleState::default_colors(cfg);
+    let swap = |color: Color32| {
+        if color == fg {
+            bg
+        } else if color == bg {
+            fg
+        } else {
+            color
+        }
+    };
+    format.color = swap(format.color);
+    format.background = swap(format.background);
+}
+
+/// Lays out every line in `lines` into a single job.
+pub fn layout(
+    lines: &VecDeque<Line>,
+    styles: &StyleTable,
+    cursor: Option<RowPosition>,
+    cfg: &Config,
+    ctx: &egui::Context,
+) -> LayoutJob {
+    let mut job = LayoutJob {
+        wrap: TextWrapping::no_max_width(),
+        ..Default::default()
+    };
+    let blink = Blink::now(cfg, ctx);
+    let mut used = Blink::default();
+
+    for (i, line) in lines.iter().enumerate() {
+        let start = job.text.len();
+        used = used.union(append_line(&mut job, line, styles, blink));
+        if let Some(cursor) = cursor
+            && cursor.row == i
+        {
+            append_cursor(&mut job, start + cursor.byte, cfg);
+        }
+        if i + 1 != lines.len() {
+            append_newline(&mut job, cfg);
+        }
+    }
+
+    used.request_repaint(cfg, ctx);
+    job
+}
+
+/// Lays out a single row, at least one cell high so rows stack evenly.
+pub fn row_job(
+    line: &Line,
+    styles: &StyleTable,
+    cursor: Option<usize>,
+    cfg: &Config,
+    blink: Blink,
+    row_height: f32,
+) -> LayoutJob {
+    let mut job = LayoutJob {
+        wrap: TextWrapping::no_max_width(),
+        first_row_min_height: row_height,
+        ..Default::default()
+    };
+    append_line(&mut job, line, styles, blink);
+    if let Some(cursor) = cursor {
+        append_cursor(&mut job, cursor, cfg);
+    }
+    job
+}
+
+/// Splits the sections of `job` so every range in `ranges` is covered by its
+/// own sections, then lets `apply` restyle them. `ranges` must be sorted and
+/// must not overlap.
+pub fn overlay(
+    job: &mut LayoutJob,
+    ranges: &[Range<usize>],
+    mut apply: impl FnMut(usize, &mut TextFormat),
+) {
+    if ranges.is_empty() {
+        return;
+    }
+    let mut sections = Vec::with_capacity(job.sections.len() + ranges.len() * 2);
+    let mut next = 0;
+    for section in std::mem::take(&mut job.sections) {
+        let LayoutSection {
+            mut leading_space,
+            byte_range,
+            format,
+        } = section;
+        let mut start = byte_range.start;
+        let end = byte_range.end;
+
+        while next < ranges.len() && ranges[next].end <= start {
+            next += 1;
+        }
+
+        while start < end {
+            let (piece_end, matched) = match ranges.get(next) {
+                Some(range) if range.start < end => {
+                    if range.start > start {
+                        (range.start, None)
+                    } else {
+                        let matched = next;
+                        if range.end <= end {
+                            next += 1;
+                        }
+                        (range.end.min(end), Some(matched))
+                    }
+                }
+                _ => (end, None),
+            };
+
+            let mut format = format.clone();
+            if let Some(i) = matched {
+                apply(i, &mut format);
+            }
+            sections.push(LayoutSection {
+                leading_space,
+                byte_range: start..piece_end,
+                format,
+            });
+            leading_space = 0.0;
+            start = piece_end;
+        }
+    }
+    job.sections = sections;
+}
@@ -0,0 +1,197 @@
+use std::{borrow::Cow, collections::VecDeque, ops::Deref};
+
+#[cfg(feature = "archive")]
+use crate::kind::archive::Archive;
+use crate::kind::line::Line;
+
+/// How much history a terminal keeps before the oldest lines are evicted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollbackLimit {
+    Lines(usize),
+    /// Total bytes allocated for the text and style runs of all lines.
+    Bytes(usize),
+}
+
+/// The rows of a terminal in a ring buffer, evicting the oldest rows once
+/// they go over a [`ScrollbackLimit`]. The most recent row is never evicted.
+///
+/// Dereferences to the rows held in memory, [`Scrollback::row`] also reaches
+/// the archived ones.
+#[derive(Default)]
+pub struct Scrollback {
+    rows: VecDeque<Line>,
+    bytes: usize,
+    /// Rows removed from the top for good, see [`Scrollback::dropped`].
+    dropped: u64,
+    on_evict: Option<Box<dyn FnMut(Line) + Send>>,
+    #[cfg(feature = "archive")]
+    archive: Option<Archive>,
+}
+
+impl std::fmt::Debug for Scrollback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scrollback")
+            .field("rows", &self.rows.len())
+            .field("bytes", &self.bytes)
+            .field("dropped", &self.dropped)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Deref for Scrollback {
+    type Target = VecDeque<Line>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rows
+    }
+}
+
+impl Scrollback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bytes used by all rows, as counted by [`ScrollbackLimit::Bytes`].
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Keeps evicted rows in `archive` so they can still be scrolled to and
+    /// searched.
+    #[cfg(feature = "archive")]
+    pub fn set_archive(&mut self, archive: Archive) {
+        self.archive = Some(archive);
+    }
+
+    #[cfg(feature = "archive")]
+    pub fn archive(&self) -> Option<&Archive> {
+        self.archive.as_ref()
+    }
+
+    /// The number of evicted rows which are still kept in an archive, they
+    /// come before the rows in memory.
+    pub fn archived(&self) -> usize {
+        #[cfg(feature = "archive")]
+        if let Some(archive) = &self.archive {
+            return archive.len();
+        }
+        0
+    }
+
+    /// Archived and in memory rows together.
+    pub fn total_rows(&self) -> usize {
+        self.archived() + self.rows.len()
+    }
+
+    /// Row `index` counting from the oldest archived row.
+    pub fn row(&self, index: usize) -> Option<Cow<'_, Line>> {
+        match index.checked_sub(self.archived()) {
+            Some(index) => self.rows.get(index).map(Cow::Borrowed),
+            #[cfg(feature = "archive")]
+            None => self.archive.as_ref()?.get(index).map(Cow::Owned),
+            #[cfg(not(feature = "archive"))]
+            None => None,
+        }
+    }
+
+    /// The number of rows removed from the top without being archived since
+    /// the scrollback was created. Every later row moved up by that many.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Every row, archived ones first.
+    pub fn all_rows(&self) -> impl Iterator<Item = Cow<'_, Line>> {
+        (0..self.archived())
+            .filter_map(|index| self.row(index))
+            .chain(self.rows.iter().map(Cow::Borrowed))
+    }
+
+    /// Called with every row that gets evicted, oldest first.
+    pub fn on_evict(&mut self, on_evict: impl FnMut(Line) + Send + 'static) {
+        self.on_evict = Some(Box::new(on_evict));
+    }
+
+    /// Adds a row at the bottom and returns how many rows were evicted from
+    /// the top to stay within `limit`.
+    pub(crate) fn push_back(&mut self, row: Line, limit: ScrollbackLimit) -> usize {
+        self.bytes += row.heap_size();
+        self.rows.push_back(row);
+        self.trim(limit)
+    }
+
+    /// Changes row `index` in place, keeping the byte count up to date.
+    pub(crate) fn edit<R>(&mut self, index: usize, edit: impl FnOnce(&mut Line) -> R) -> R {
+        let row = &mut self.rows[index];
+        let before = row.heap_size();
+        let result = edit(row);
+        self.bytes = self.bytes - before + row.heap_size();
+        result
+    }
+
+    /// Changes the last row, adding an empty one first if there are none.
+    pub(crate) fn edit_back<R>(&mut self, edit: impl FnOnce(&mut Line) -> R) -> R {
+        if self.rows.is_empty() {
+            self.rows.push_back(Line::default());
+        }
+        self.edit(self.rows.len() - 1, edit)
+    }
+
+    /// Gives `edit` all rows at once, for changes like reflowing which touch
+    /// most of them.
+    pub(crate) fn edit_all<R>(&mut self, edit: impl FnOnce(&mut VecDeque<Line>) -> R) -> R {
+        let result = edit(&mut self.rows);
+        self.bytes = self.rows.iter().map(Line::heap_size).sum();
+        result
+    }
+
+    /// Evicts rows from the top until the rest fit in `limit`, returning how
+    /// many were evicted.
+    pub(crate) fn trim(&mut self, limit: ScrollbackLimit) -> usize {
+        let mut evicted = 0;
+        while self.rows.len() > 1 && self.over(limit) {
+            // `VecDeque` is a ring buffer, popping the front is O(1)
+            let Some(row) = self.rows.pop_front() else {
+                break;
+            };
+            self.bytes -= row.heap_size();
+            evicted += 1;
+            let archived = self.archived();
+            #[cfg(feature = "archive")]
+            if let Some(archive) = &mut self.archive
+                && archive.push(&row).is_err()
+            {
+                // stop archiving instead of leaving a gap in the history
+                self.archive = None;
+            }
+            // the row is gone for good, and so is the archive if writing to
+            // it just failed
+            if self.archived() != archived + 1 {
+                self.dropped += archived as u64 + 1;
+            }
+            if let Some(on_evict) = &mut self.on_evict {
+                on_evict(row);
+            }
+        }
+        evicted
+    }
+
+    /// Drops every row without evicting them and leaves a single empty one.
+    pub(crate) fn clear(&mut self) {
+        self.dropped += self.total_rows() as u64;
+        #[cfg(feature = "archive")]
+        if let Some(archive) = &mut self.archive {
+            archive.clear();
+        }
+        self.rows.clear();
+        self.rows.push_back(Line::default());
+        self.bytes = 0;
+    }
+
+    fn over(&self, limit: ScrollbackLimit) -> bool {
+        match limit {
+            ScrollbackLimit::Lines(lines) => self.rows.len() > lines.max(1),
+            ScrollbackLimit::Bytes(bytes) => self.bytes > bytes,
+        }
+    }
+}
@@ -0,0 +1,267 @@
+/// Records the raw bytes of escape sequences as they stream past the
+/// [`ansi::AnsiParser`] so kinds can act on sequences it doesn't know about
+/// (DEC private modes, OSC commands, charset designations, ...).
+#[derive(Debug, Default)]
+pub struct Recorder {
+    buf: Vec<u8>,
+    state: State,
+    /// The string went over [`MAX_STR_LEN`] and is dropped once it ends.
+    overflowed: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    EscapeIntermediate,
+    Csi,
+    Str { osc: bool },
+    StrEscape { osc: bool },
+}
+
+const MAX_CSI_LEN: usize = 64;
+/// Longer strings are dropped rather than acted on cut short.
+const MAX_STR_LEN: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Sequence<'a> {
+    Csi(Csi<'a>),
+    Osc(Osc<'a>),
+    Esc {
+        intermediate: Option<u8>,
+        final_byte: u8,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Csi<'a> {
+    pub private: Option<u8>,
+    params: &'a [u8],
+    pub intermediates: &'a [u8],
+    pub final_byte: u8,
+}
+
+impl<'a> Csi<'a> {
+    /// The numeric parameters, missing ones are reported as `0`.
+    /// Sub-parameters separated by `:` are skipped.
+    pub fn params(&self) -> impl Iterator<Item = u16> + 'a {
+        let params: &'a [u8] = self.params;
+        params.split(|b| *b == b';').map(|param| {
+            param
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .fold(0u16, |acc, b| {
+                    acc.saturating_mul(10).saturating_add((b - b'0') as u16)
+                })
+        })
+    }
+
+    pub fn param(&self, index: usize, default: u16) -> u16 {
+        match self.params().nth(index) {
+            Some(0) | None => default,
+            Some(v) => v,
+        }
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.private == Some(b'?')
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Osc<'a> {
+    pub command: u16,
+    pub data: &'a [u8],
+}
+
+impl<'a> Osc<'a> {
+    fn parse(raw: &'a [u8]) -> Option<Self> {
+        let (command, data) = match raw.iter().position(|b| *b == b';') {
+            Some(at) => (&raw[..at], &raw[at + 1..]),
+            None => (raw, &[][..]),
+        };
+        let command = std::str::from_utf8(command).ok()?.parse().ok()?;
+        Some(Self { command, data })
+    }
+
+    pub fn data_str(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.data).ok()
+    }
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the next byte is outside of any sequence, so a BEL is a bell
+    /// rather than the end of a string.
+    pub fn is_ground(&self) -> bool {
+        self.state == State::Ground
+    }
+
+    pub fn push(&mut self, b: u8) -> Option<Sequence<'_>> {
+        match self.state {
+            State::Ground => {
+                if b == 0x1b {
+                    self.enter(State::Escape);
+                }
+                None
+            }
+            State::Escape => self.escape(b),
+            State::EscapeIntermediate => match b {
+                0x20..=0x2f => {
+                    self.buf.push(b);
+                    None
+                }
+                0x30..=0x7e => {
+                    self.state = State::Ground;
+                    Some(Sequence::Esc {
+                        intermediate: self.buf.first().copied(),
+                        final_byte: b,
+                    })
+                }
+                0x1b => {
+                    self.enter(State::Escape);
+                    None
+                }
+                _ => {
+                    self.state = State::Ground;
+                    None
+                }
+            },
+            State::Csi => match b {
+                0x40..=0x7e => {
+                    self.state = State::Ground;
+                    Some(Sequence::Csi(self.csi(b)))
+                }
+                0x20..=0x3f if self.buf.len() < MAX_CSI_LEN => {
+                    self.buf.push(b);
+                    None
+                }
+                0x1b => {
+                    self.enter(State::Escape);
+                    None
+                }
+                0x18 | 0x1a => {
+                    self.state = State::Ground;
+                    None
+                }
+                _ => None,
+            },
+            State::Str { osc } => match b {
+                0x07 => {
+                    self.state = State::Ground;
+                    self.finish_str(osc)
+                }
+                0x1b => {
+                    self.state = State::StrEscape { osc };
+                    None
+                }
+                0x18 | 0x1a => {
+                    self.state = State::Ground;
+                    None
+                }
+                _ => {
+                    if self.buf.len() < MAX_STR_LEN {
+                        self.buf.push(b);
+                    } else {
+                        self.overflowed = true;
+                    }
+                    None
+                }
+            },
+            State::StrEscape { osc } => {
+                if b == b'\\' {
+                    self.state = State::Ground;
+                    self.finish_str(osc)
+                } else {
+                    self.enter(State::Escape);
+                    self.escape(b)
+                }
+            }
+        }
+    }
+
+    fn enter(&mut self, state: State) {
+        self.buf.clear();
+        self.overflowed = false;
+        self.state = state;
+    }
+
+    fn escape(&mut self, b: u8) -> Option<Sequence<'_>> {
+        match b {
+            b'[' => self.enter(State::Csi),
+            b']' => self.enter(State::Str { osc: true }),
+            b'P' | b'X' | b'^' | b'_' => self.enter(State::Str { osc: false }),
+            0x20..=0x2f => {
+                self.enter(State::EscapeIntermediate);
+                self.buf.push(b);
+            }
+            0x30..=0x7e => {
+                self.state = State::Ground;
+                return Some(Sequence::Esc {
+                    intermediate: None,
+                    final_byte: b,
+                });
+            }
+            0x1b => self.enter(State::Escape),
+            _ => self.state = State::Ground,
+        }
+        None
+    }
+
+    fn csi(&self, final_byte: u8) -> Csi<'_> {
+        let raw = &self.buf[..];
+        let (private, raw) = match raw.first() {
+            Some(p @ b'<'..=b'?') => (Some(*p), &raw[1..]),
+            _ => (None, raw),
+        };
+        let split = raw
+            .iter()
+            .position(|b| (0x20..=0x2f).contains(b))
+            .unwrap_or(raw.len());
+        Csi {
+            private,
+            params: &raw[..split],
+            intermediates: &raw[split..],
+            final_byte,
+        }
+    }
+
+    fn finish_str(&self, osc: bool) -> Option<Sequence<'_>> {
+        if osc && !self.overflowed {
+            Osc::parse(&self.buf).map(Sequence::Osc)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn osc_command(recorder: &mut Recorder, bytes: &[u8]) -> Option<u16> {
+        let mut command = None;
+        for &b in bytes {
+            if let Some(Sequence::Osc(osc)) = recorder.push(b) {
+                command = Some(osc.command);
+            }
+        }
+        command
+    }
+
+    #[test]
+    fn over_long_strings_are_dropped() {
+        let mut recorder = Recorder::new();
+        let mut long = b"\x1b]52;c;".to_vec();
+        long.resize(MAX_STR_LEN + 16, b'A');
+        long.push(0x07);
+        assert_eq!(osc_command(&mut recorder, &long), None);
+        assert!(recorder.is_ground());
+        // the next string is recorded again
+        assert_eq!(osc_command(&mut recorder, b"\x1b]52;c;QQ==\x07"), Some(52));
+    }
+}
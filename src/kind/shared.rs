@@ -0,0 +1,90 @@
+use crate::{
+    Config,
+    kind::{
+        charset::Charsets,
+        line,
+        link::{LinkId, LinkTable},
+        modes::Modes,
+        sequence::Sequence,
+        title::Title,
+    },
+};
+
+/// Tab stops are fixed every this many columns.
+pub(crate) const TAB_WIDTH: usize = 8;
+
+/// The 1-based column of the first tab stop after `column`.
+pub(crate) fn next_tab_stop(column: usize) -> usize {
+    (column - 1) / TAB_WIDTH * TAB_WIDTH + TAB_WIDTH + 1
+}
+
+/// State both kinds keep and change the same way, whatever their cursor
+/// does.
+#[derive(Debug, Default)]
+pub(crate) struct Shared {
+    /// The hyperlink opened by the last OSC 8, given to written cells.
+    pub(crate) link: Option<LinkId>,
+    pub(crate) links: LinkTable,
+    pub(crate) title: Title,
+    pub(crate) modes: Modes,
+    pub(crate) charsets: Charsets,
+}
+
+impl Shared {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn sequence(&mut self, seq: Sequence<'_>) {
+        match seq {
+            Sequence::Csi(csi) => {
+                self.modes.csi(&csi);
+                self.title.csi(&csi);
+            }
+            Sequence::Osc(osc) if osc.command == 8 => self.link = self.links.osc(&osc),
+            Sequence::Osc(osc) => self.title.osc(&osc),
+            Sequence::Esc {
+                intermediate,
+                final_byte,
+            } => self.charsets.esc(intermediate, final_byte),
+        }
+    }
+
+    /// Handles the C0 controls which don't move the cursor and returns what
+    /// the kind has to do for the rest.
+    pub(crate) fn control(&mut self, c0: u8, cfg: &Config) -> Option<Control> {
+        match c0 {
+            // VT and FF move down like LF
+            b'\n' | 0x0b | 0x0c => Some(Control::LineFeed),
+            b'\r' => Some(Control::CarriageReturn),
+            0x08 => Some(Control::Backspace),
+            b'\t' => Some(Control::Tab),
+            0x0e => {
+                self.charsets.shift(true);
+                None
+            }
+            0x0f => {
+                self.charsets.shift(false);
+                None
+            }
+            // the bell is rung by the terminal, which knows the callbacks
+            0x07 => None,
+            _ if cfg.show_control_pictures => Some(Control::Picture(line::control_picture(c0))),
+            _ => None,
+        }
+    }
+}
+
+/// A C0 control moving the cursor, which each kind does its own way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Control {
+    /// LF, or VT and FF which do the same.
+    LineFeed,
+    CarriageReturn,
+    Backspace,
+    /// Move to the [`next_tab_stop`].
+    Tab,
+    /// Write the symbol of an otherwise ignored control, with
+    /// [`Config::show_control_pictures`].
+    Picture(char),
+}
@@ -1,7 +1,63 @@
+use std::collections::HashMap;
+
 use crate::Config;
 use ansi::{Color, SelectGraphic};
 use egui::{Color32, FontId, Stroke, TextFormat};
 
+/// Index of a format in a [`StyleTable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StyleId(pub(crate) u32);
+
+/// `TextFormat` only implements `PartialEq` because of its floats, none of
+/// which are ever NaN here.
+#[derive(Debug, Clone, PartialEq)]
+struct Interned(TextFormat);
+
+impl Eq for Interned {}
+
+impl std::hash::Hash for Interned {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+/// Every distinct format a terminal has used, so lines only have to store
+/// a [`StyleId`] per run instead of a whole `TextFormat`.
+#[derive(Debug, Default)]
+pub struct StyleTable {
+    formats: Vec<TextFormat>,
+    ids: HashMap<Interned, StyleId>,
+}
+
+impl StyleTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, format: TextFormat) -> StyleId {
+        let key = Interned(format);
+        if let Some(id) = self.ids.get(&key) {
+            return *id;
+        }
+        let id = StyleId(self.formats.len() as u32);
+        self.formats.push(key.0.clone());
+        self.ids.insert(key, id);
+        id
+    }
+
+    pub fn get(&self, id: StyleId) -> &TextFormat {
+        &self.formats[id.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.formats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formats.is_empty()
+    }
+}
+
 #[derive(Debug, Default)]
 pub enum Underline {
     #[default]
@@ -48,6 +104,9 @@ pub struct StyleState {
     pub invert_fg_bg: bool,
     pub strike_through: bool,
     pub conceal: bool,
+
+    /// The interned format of the current state, reset by [`StyleState::sg`].
+    id: Option<StyleId>,
 }
 
 impl StyleState {
@@ -70,6 +129,7 @@ impl StyleState {
     }
 
     pub fn sg(&mut self, sg: SelectGraphic) {
+        self.id = None;
         match sg {
             SelectGraphic::Reset => {
                 self.fg = Color::Default;
@@ -119,6 +179,26 @@ impl StyleState {
         }
     }
 
+    /// The id of [`StyleState::format`] in `table`, only interned again
+    /// after the state changed.
+    pub fn id(&mut self, table: &mut StyleTable, cfg: &Config) -> StyleId {
+        if let Some(id) = self.id {
+            return id;
+        }
+        let id = table.intern(self.format(cfg));
+        self.id = Some(id);
+        id
+    }
+
+    /// The foreground and background cells without SGR colors are drawn
+    /// with.
+    pub fn default_colors(cfg: &Config) -> (Color32, Color32) {
+        (
+            Self::color_convert(Color::Default, false, cfg),
+            Self::color_convert(Color::Default, true, cfg),
+        )
+    }
+
     fn color_convert(color: ansi::Color, background: bool, cfg: &Config) -> egui::Color32 {
         match color.flatten_vga() {
             ansi::Color::Default => {
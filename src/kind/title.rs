@@ -0,0 +1,164 @@
+use crate::kind::sequence::{Csi, Osc};
+
+/// xterm keeps this many entries on its title stack.
+const MAX_STACK: usize = 10;
+
+/// The window title and icon name set by the program, with the XTWINOPS
+/// title stack.
+#[derive(Debug, Default, Clone)]
+pub struct Title {
+    title: String,
+    icon_name: String,
+    stack: Vec<(String, String)>,
+    /// Bumped whenever the title or icon name changes.
+    revision: u64,
+}
+
+impl Title {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn icon_name(&self) -> &str {
+        &self.icon_name
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Handles `OSC 0`, `OSC 1` and `OSC 2`.
+    pub fn osc(&mut self, osc: &Osc<'_>) {
+        let Some(text) = osc.data_str() else {
+            return;
+        };
+        let (icon, title) = match osc.command {
+            0 => (true, true),
+            1 => (true, false),
+            2 => (false, true),
+            _ => return,
+        };
+        if icon {
+            self.icon_name = text.to_owned();
+        }
+        if title {
+            self.title = text.to_owned();
+        }
+        self.revision += 1;
+    }
+
+    /// Handles XTWINOPS `CSI 22 ; Ps t` and `CSI 23 ; Ps t`, pushing and
+    /// popping the icon name (`Ps` 1), title (2) or both (0).
+    pub fn csi(&mut self, csi: &Csi<'_>) {
+        if csi.final_byte != b't' || csi.private.is_some() || !csi.intermediates.is_empty() {
+            return;
+        }
+        let which = csi.params().nth(1).unwrap_or(0);
+        let (icon, title) = (which != 2, which != 1);
+        match csi.param(0, 0) {
+            22 => {
+                if self.stack.len() == MAX_STACK {
+                    self.stack.remove(0);
+                }
+                // both are saved, `Ps` only matters for what gets restored
+                self.stack
+                    .push((self.icon_name.clone(), self.title.clone()));
+            }
+            23 => {
+                let Some((icon_name, title_text)) = self.stack.pop() else {
+                    return;
+                };
+                if icon {
+                    self.icon_name = icon_name;
+                }
+                if title {
+                    self.title = title_text;
+                }
+                self.revision += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kind::sequence::{Recorder, Sequence};
+
+    fn feed(title: &mut Title, bytes: &[u8]) {
+        let mut recorder = Recorder::new();
+        for &b in bytes {
+            match recorder.push(b) {
+                Some(Sequence::Csi(csi)) => title.csi(&csi),
+                Some(Sequence::Osc(osc)) => title.osc(&osc),
+                _ => {}
+            }
+        }
+    }
+
+    fn titles(title: &Title) -> (&str, &str) {
+        (title.icon_name(), title.title())
+    }
+
+    #[test]
+    fn icon_name_and_title_are_set_separately() {
+        let mut title = Title::new();
+        feed(&mut title, b"\x1b]0;both\x07");
+        assert_eq!(titles(&title), ("both", "both"));
+        feed(&mut title, b"\x1b]1;icon\x07");
+        assert_eq!(titles(&title), ("icon", "both"));
+        feed(&mut title, b"\x1b]2;window\x1b\\");
+        assert_eq!(titles(&title), ("icon", "window"));
+        assert_eq!(title.revision(), 3);
+    }
+
+    #[test]
+    fn pop_restores_what_was_asked_for() {
+        let mut title = Title::new();
+        feed(&mut title, b"\x1b]1;icon\x07\x1b]2;window\x07\x1b[22t");
+        feed(&mut title, b"\x1b]0;changed\x07");
+
+        feed(&mut title, b"\x1b[22t\x1b[23;1t");
+        assert_eq!(titles(&title), ("changed", "changed"));
+
+        feed(&mut title, b"\x1b[23;2t");
+        assert_eq!(titles(&title), ("changed", "window"));
+
+        feed(
+            &mut title,
+            b"\x1b]0;changed\x07\x1b[22;0t\x1b]0;again\x07\x1b[23;0t",
+        );
+        assert_eq!(titles(&title), ("changed", "changed"));
+    }
+
+    #[test]
+    fn popping_an_empty_stack_keeps_the_titles() {
+        let mut title = Title::new();
+        feed(&mut title, b"\x1b]0;kept\x07");
+        let revision = title.revision();
+        feed(&mut title, b"\x1b[23t");
+        assert_eq!(titles(&title), ("kept", "kept"));
+        assert_eq!(title.revision(), revision);
+    }
+
+    #[test]
+    fn stack_keeps_the_latest_entries() {
+        let mut title = Title::new();
+        for i in 0..MAX_STACK + 2 {
+            feed(&mut title, format!("\x1b]0;{i}\x07\x1b[22t").as_bytes());
+        }
+        assert_eq!(title.stack.len(), MAX_STACK);
+        for i in (2..MAX_STACK + 2).rev() {
+            feed(&mut title, b"\x1b[23t");
+            assert_eq!(title.title(), i.to_string());
+        }
+        // the oldest two were dropped
+        feed(&mut title, b"\x1b[23t");
+        assert_eq!(title.title(), "2");
+    }
+}
@@ -1,8 +1,26 @@
 #![forbid(unsafe_code)]
 
+#[cfg(any(feature = "tokio", feature = "futures"))]
+pub mod async_io;
+mod bell;
+pub mod clipboard;
 pub mod config;
+mod grid;
+pub mod handle;
+pub mod input;
 pub mod kind;
+pub mod links;
+#[cfg(feature = "log")]
+pub mod logger;
+pub mod mouse;
+#[cfg(feature = "pty")]
+pub mod pty;
+pub mod search;
 pub mod term;
+#[cfg(feature = "tracing")]
+pub mod tracing_layer;
+mod view;
 
+pub use clipboard::{ClipboardPolicy, ClipboardRequest};
 pub use config::*;
 pub use term::*;
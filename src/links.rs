@@ -0,0 +1,189 @@
+use std::ops::Range;
+
+use regex::Regex;
+
+use crate::kind::link::LinkId;
+
+/// What a detected link points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkKind {
+    Url,
+    /// `path/to/file.rs:12:5` and the like.
+    FileLocation,
+    /// Found by a user supplied [`LinkRule`].
+    Custom(String),
+}
+
+/// Text matching `regex` is treated as a link of the given kind.
+#[derive(Debug, Clone)]
+pub struct LinkRule {
+    pub kind: LinkKind,
+    pub regex: Regex,
+}
+
+impl LinkRule {
+    pub fn new(kind: LinkKind, regex: Regex) -> Self {
+        Self { kind, regex }
+    }
+
+    pub fn url() -> Self {
+        Self::new(
+            LinkKind::Url,
+            Regex::new(r#"\b(?:https?|ftp|file)://[^\s<>"'`]*[^\s<>"'`.,:;!?)\]}]"#)
+                .expect("valid url regex"),
+        )
+    }
+
+    /// Compiler style locations, a path and a line number with an optional
+    /// column.
+    ///
+    /// Without a directory a name like `example.com:8080` could as well be a
+    /// host and port, so then the file needs an extension starting with a
+    /// letter and both a line and a column, like `main.c:12:5`.
+    pub fn file_location() -> Self {
+        Self::new(
+            LinkKind::FileLocation,
+            Regex::new(concat!(
+                r"(?:[\w.\-~]*[/\\])+[\w.\-]*[A-Za-z_][\w.\-]*:\d+(?::\d+)?",
+                r"|\b[\w\-]+(?:\.[\w\-]+)*\.[A-Za-z]\w*:\d+:\d+",
+            ))
+            .expect("valid file location regex"),
+        )
+    }
+}
+
+/// The link under the pointer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Hovered {
+    /// An OSC 8 hyperlink, which can span several rows.
+    Hyperlink(LinkId),
+    /// Text matched by a [`LinkRule`] on a single row.
+    Detected {
+        row: usize,
+        range: Range<usize>,
+        text: String,
+        kind: LinkKind,
+    },
+}
+
+type OnHyperlink = Box<dyn FnMut(&str) -> bool + Send>;
+type OnLink = Box<dyn FnMut(&str, &LinkKind) -> bool + Send>;
+
+/// Hover and click state of the links in a terminal.
+pub(crate) struct Links {
+    pub(crate) hovered: Option<Hovered>,
+    pub(crate) rules: Vec<LinkRule>,
+    on_hyperlink: Option<OnHyperlink>,
+    on_link: Option<OnLink>,
+}
+
+impl Default for Links {
+    fn default() -> Self {
+        Self {
+            hovered: None,
+            rules: vec![LinkRule::url(), LinkRule::file_location()],
+            on_hyperlink: None,
+            on_link: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for Links {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Links")
+            .field("hovered", &self.hovered)
+            .field("rules", &self.rules)
+            .field("on_hyperlink", &self.on_hyperlink.is_some())
+            .field("on_link", &self.on_link.is_some())
+            .finish()
+    }
+}
+
+impl Links {
+    pub(crate) fn set_on_hyperlink(
+        &mut self,
+        on_hyperlink: impl FnMut(&str) -> bool + Send + 'static,
+    ) {
+        self.on_hyperlink = Some(Box::new(on_hyperlink));
+    }
+
+    pub(crate) fn set_on_link(
+        &mut self,
+        on_link: impl FnMut(&str, &LinkKind) -> bool + Send + 'static,
+    ) {
+        self.on_link = Some(Box::new(on_link));
+    }
+
+    /// The first match of any rule in `text` which covers byte `at`.
+    pub(crate) fn detect(&self, text: &str, at: usize) -> Option<(Range<usize>, LinkKind)> {
+        self.rules.iter().find_map(|rule| {
+            rule.regex
+                .find_iter(text)
+                .map(|m| m.range())
+                .find(|range| range.contains(&at))
+                .map(|range| (range, rule.kind.clone()))
+        })
+    }
+
+    /// Lets the callback handle a click on `uri`, or opens it in a browser.
+    pub(crate) fn open_hyperlink(&mut self, ctx: &egui::Context, uri: &str) {
+        let handled = self
+            .on_hyperlink
+            .as_mut()
+            .is_some_and(|on_hyperlink| on_hyperlink(uri));
+        if !handled {
+            ctx.open_url(egui::OpenUrl::new_tab(uri));
+        }
+    }
+
+    /// Lets the callback handle a click on a detected link, URLs it doesn't
+    /// handle are opened in a browser.
+    pub(crate) fn open_detected(&mut self, ctx: &egui::Context, text: &str, kind: &LinkKind) {
+        let handled = self
+            .on_link
+            .as_mut()
+            .is_some_and(|on_link| on_link(text, kind));
+        if !handled && *kind == LinkKind::Url {
+            ctx.open_url(egui::OpenUrl::new_tab(text));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_locations(text: &str) -> Vec<&str> {
+        LinkRule::file_location()
+            .regex
+            .find_iter(text)
+            .map(|m| m.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn file_locations_with_a_directory() {
+        assert_eq!(
+            file_locations("--> src/kind/line.rs:120:9"),
+            ["src/kind/line.rs:120:9"]
+        );
+        assert_eq!(file_locations("at ./Makefile:12"), ["./Makefile:12"]);
+        assert_eq!(file_locations(r"src\main.c:3"), [r"src\main.c:3"]);
+        assert_eq!(file_locations("~/notes.txt:4:"), ["~/notes.txt:4"]);
+    }
+
+    #[test]
+    fn file_locations_without_a_directory_need_a_column() {
+        assert_eq!(file_locations("main.c:12:5: error"), ["main.c:12:5"]);
+        assert!(file_locations("main.c:12").is_empty());
+    }
+
+    #[test]
+    fn hosts_are_not_file_locations() {
+        assert!(file_locations("listening on example.com:8080").is_empty());
+        assert!(file_locations("connected to 192.168.0.1:443").is_empty());
+        assert!(file_locations("at 10.0.0.1:80:1").is_empty());
+        assert!(file_locations("localhost:3000").is_empty());
+        assert!(file_locations("ratio 3/4:15").is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use regex::Regex;
+
+use crate::{LevelColors, config::Severity, handle::TerminalHandle};
+
+/// Which records get through, parsed from `env_logger` style strings like
+/// `warn,my_crate=debug,my_crate::net=trace/timeout`.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    /// Module path prefixes and their levels, `None` applies to everything.
+    directives: Vec<(Option<String>, LevelFilter)>,
+    /// Messages have to match this.
+    pattern: Option<Regex>,
+}
+
+impl Default for Filter {
+    /// Lets every error through, like `env_logger` without `RUST_LOG`.
+    fn default() -> Self {
+        Self {
+            directives: vec![(None, LevelFilter::Error)],
+            pattern: None,
+        }
+    }
+}
+
+impl Filter {
+    /// Parses a comma separated list of `level`, `path` and `path=level`
+    /// directives, optionally followed by `/regex`. Invalid directives are
+    /// skipped.
+    pub fn parse(spec: &str) -> Self {
+        let (directives, pattern) = match spec.split_once('/') {
+            Some((directives, pattern)) => (directives, Regex::new(pattern).ok()),
+            None => (spec, None),
+        };
+
+        let mut filter = Self {
+            directives: Vec::new(),
+            pattern,
+        };
+        for directive in directives.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+            match directive.split_once('=') {
+                Some((path, level)) => {
+                    if let Ok(level) = level.trim().parse() {
+                        filter.directives.push((Some(path.trim().to_owned()), level));
+                    }
+                }
+                None => match directive.parse() {
+                    Ok(level) => filter.directives.push((None, level)),
+                    // a bare path enables everything in it
+                    Err(_) => filter
+                        .directives
+                        .push((Some(directive.to_owned()), LevelFilter::Trace)),
+                },
+            }
+        }
+        if filter.directives.is_empty() {
+            filter.directives.push((None, LevelFilter::Error));
+        }
+        filter
+    }
+
+    /// The level for `target`, the most specific directive wins.
+    pub fn level(&self, target: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .filter(|(path, _)| match path {
+                None => true,
+                Some(path) => {
+                    target == path
+                        || target
+                            .strip_prefix(path.as_str())
+                            .is_some_and(|rest| rest.starts_with("::"))
+                }
+            })
+            .max_by_key(|(path, _)| path.as_ref().map_or(0, |path| path.len() + 1))
+            .map_or(LevelFilter::Off, |(_, level)| *level)
+    }
+
+    /// The most verbose level any directive lets through.
+    pub fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|(_, level)| *level)
+            .max()
+            .unwrap_or(LevelFilter::Off)
+    }
+
+    fn matches(&self, record: &Record<'_>) -> bool {
+        if record.level() > self.level(record.target()) {
+            return false;
+        }
+        match &self.pattern {
+            Some(pattern) => pattern.is_match(&record.args().to_string()),
+            None => true,
+        }
+    }
+}
+
+/// A `log` backend writing records into one or more terminals.
+#[derive(Debug)]
+pub struct TerminalLogger {
+    handles: Vec<TerminalHandle>,
+    filter: Filter,
+    colors: LevelColors,
+}
+
+impl TerminalLogger {
+    pub fn new(handle: TerminalHandle) -> Self {
+        Self {
+            handles: vec![handle],
+            filter: Filter::default(),
+            colors: LevelColors::default(),
+        }
+    }
+
+    /// Writes records into `handle` as well.
+    pub fn with_terminal(mut self, handle: TerminalHandle) -> Self {
+        self.handles.push(handle);
+        self
+    }
+
+    /// See [`Filter::parse`].
+    pub fn with_filter(mut self, spec: &str) -> Self {
+        self.filter = Filter::parse(spec);
+        self
+    }
+
+    pub fn with_colors(mut self, colors: LevelColors) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// Installs the logger as the global `log` backend.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.filter.max_level());
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+impl From<Level> for Severity {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Error => Self::Error,
+            Level::Warn => Self::Warn,
+            Level::Info => Self::Info,
+            Level::Debug => Self::Debug,
+            Level::Trace => Self::Trace,
+        }
+    }
+}
+
+impl Log for TerminalLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.filter.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.filter.matches(record) {
+            return;
+        }
+        let (color, level) = self.colors.level(record.level().into());
+        let line = format!(
+            "\x1b[1;{color}m{level}\x1b[0m \x1b[2m{}:\x1b[0m {}\r\n",
+            record.target(),
+            record.args()
+        );
+        for handle in &self.handles {
+            handle.write_str(&line);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Sends `log` records matching `filter` to the terminal behind `handle`.
+///
+/// ```ignore
+/// egui_ansi::logger::init(term.handle(), "info,my_crate=debug")?;
+/// ```
+pub fn init(handle: TerminalHandle, filter: &str) -> Result<(), SetLoggerError> {
+    TerminalLogger::new(handle).with_filter(filter).init()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_level_applies_everywhere() {
+        let filter = Filter::parse("warn");
+        assert_eq!(filter.level("my_crate"), LevelFilter::Warn);
+        assert_eq!(filter.level("other::module"), LevelFilter::Warn);
+        assert_eq!(filter.max_level(), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn path_directives() {
+        let filter = Filter::parse("error, my_crate = debug, other");
+        assert_eq!(filter.level("my_crate"), LevelFilter::Debug);
+        assert_eq!(filter.level("my_crate::net"), LevelFilter::Debug);
+        // a prefix only counts at a module boundary
+        assert_eq!(filter.level("my_crate_macros"), LevelFilter::Error);
+        assert_eq!(filter.level("other"), LevelFilter::Trace);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+
+        // without a bare level nothing else gets through
+        assert_eq!(Filter::parse("my_crate=info").level("other"), LevelFilter::Off);
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let filter = Filter::parse("my_crate::net=trace,my_crate=info,warn");
+        assert_eq!(filter.level("my_crate::net::tcp"), LevelFilter::Trace);
+        assert_eq!(filter.level("my_crate::ui"), LevelFilter::Info);
+        assert_eq!(filter.level("dep"), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn malformed_directives_are_skipped() {
+        let filter = Filter::parse("my_crate=loud,,info");
+        assert_eq!(filter.level("my_crate"), LevelFilter::Info);
+
+        for spec in ["", " , ", "=", "a=b=c"] {
+            let filter = Filter::parse(spec);
+            assert_eq!(filter.level("my_crate"), LevelFilter::Error, "{spec:?}");
+        }
+    }
+
+    #[test]
+    fn pattern_filters_messages() {
+        let matches = |filter: &Filter, level, message| {
+            filter.matches(
+                &Record::builder()
+                    .level(level)
+                    .target("my_crate")
+                    .args(format_args!("{message}"))
+                    .build(),
+            )
+        };
+        let filter = Filter::parse("info/time(out)?");
+        assert!(matches(&filter, Level::Info, "connection timeout"));
+        assert!(!matches(&filter, Level::Info, "connected"));
+        assert!(!matches(&filter, Level::Debug, "timeout"));
+
+        // an invalid pattern lets every message through
+        let filter = Filter::parse("info/(");
+        assert!(matches(&filter, Level::Info, "connected"));
+    }
+}
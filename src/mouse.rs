@@ -0,0 +1,304 @@
+use egui::{Event, Modifiers, MouseWheelUnit, PointerButton, Pos2};
+
+use crate::kind::modes::{Modes, MouseEncoding, MouseTracking};
+
+const RELEASE: u8 = 3;
+const MOTION: u8 = 32;
+const WHEEL_UP: u8 = 64;
+const WHEEL_DOWN: u8 = 65;
+
+/// Zero based cell coordinates inside the visible screen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub row: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct MouseState {
+    held: Option<u8>,
+    last_cell: Option<Cell>,
+    wheel: f32,
+}
+
+fn button_code(button: PointerButton) -> Option<u8> {
+    match button {
+        PointerButton::Primary => Some(0),
+        PointerButton::Middle => Some(1),
+        PointerButton::Secondary => Some(2),
+        _ => None,
+    }
+}
+
+fn modifier_bits(modifiers: Modifiers) -> u8 {
+    (modifiers.shift as u8) * 4 + (modifiers.alt as u8) * 8 + (modifiers.ctrl as u8) * 16
+}
+
+/// Appends a single mouse report to `out`.
+pub fn encode(out: &mut Vec<u8>, code: u8, cell: Cell, release: bool, encoding: MouseEncoding) {
+    let x = cell.column + 1;
+    let y = cell.row + 1;
+    match encoding {
+        MouseEncoding::Sgr => {
+            out.extend_from_slice(
+                format!("\x1b[<{code};{x};{y}{}", if release { 'm' } else { 'M' }).as_bytes(),
+            );
+        }
+        MouseEncoding::Default | MouseEncoding::Utf8 => {
+            // the legacy encodings can't tell which button was released
+            let code = if release { RELEASE | (code & !3) } else { code };
+            out.extend_from_slice(b"\x1b[M");
+            out.push(32 + code);
+            for v in [x, y] {
+                if encoding == MouseEncoding::Utf8 {
+                    let c = char::from_u32((32 + v).min(2047) as u32).unwrap_or(' ');
+                    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                } else {
+                    out.push((32 + v).min(255) as u8);
+                }
+            }
+        }
+    }
+}
+
+impl MouseState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Translates a pointer event into reports for the attached program.
+    /// Returns `false` if the event should be handled locally instead, which
+    /// is the case when reporting is off or Shift is held.
+    pub fn handle(
+        &mut self,
+        event: &Event,
+        modes: &Modes,
+        row_height: f32,
+        to_cell: impl Fn(Pos2) -> Option<Cell>,
+        out: &mut Vec<u8>,
+    ) -> bool {
+        let tracking = modes.mouse_tracking;
+        if tracking == MouseTracking::Off {
+            self.held = None;
+            return false;
+        }
+        let x10 = tracking == MouseTracking::X10;
+        let encoding = modes.mouse_encoding;
+        let mods = |modifiers| if x10 { 0 } else { modifier_bits(modifiers) };
+
+        match event {
+            Event::PointerButton {
+                pos,
+                button,
+                pressed,
+                modifiers,
+            } => {
+                if modifiers.shift {
+                    return false;
+                }
+                let Some(code) = button_code(*button) else {
+                    return false;
+                };
+                if *pressed {
+                    let Some(cell) = to_cell(*pos) else {
+                        return false;
+                    };
+                    self.held = Some(code);
+                    self.last_cell = Some(cell);
+                    encode(out, code | mods(*modifiers), cell, false, encoding);
+                } else {
+                    if self.held.take().is_none() {
+                        return false;
+                    }
+                    if !x10 {
+                        let cell = to_cell(*pos).or(self.last_cell).unwrap_or_default();
+                        encode(out, code | mods(*modifiers), cell, true, encoding);
+                    }
+                }
+                true
+            }
+            Event::PointerMoved(pos) => {
+                let report = match tracking {
+                    MouseTracking::ButtonEvent => self.held.is_some(),
+                    MouseTracking::AnyEvent => true,
+                    _ => false,
+                };
+                let Some(cell) = to_cell(*pos) else {
+                    return false;
+                };
+                if report && self.last_cell != Some(cell) {
+                    let code = self.held.unwrap_or(RELEASE) | MOTION;
+                    encode(out, code, cell, false, encoding);
+                }
+                self.last_cell = Some(cell);
+                report
+            }
+            Event::MouseWheel {
+                unit,
+                delta,
+                modifiers,
+                ..
+            } => {
+                if modifiers.shift || x10 {
+                    return false;
+                }
+                let Some(cell) = self.last_cell else {
+                    return false;
+                };
+                self.wheel += match unit {
+                    MouseWheelUnit::Point => delta.y / row_height.max(1.0),
+                    MouseWheelUnit::Line | MouseWheelUnit::Page => delta.y,
+                };
+                while self.wheel.abs() >= 1.0 {
+                    let code = if self.wheel > 0.0 { WHEEL_UP } else { WHEEL_DOWN };
+                    self.wheel -= self.wheel.signum();
+                    encode(out, code | mods(*modifiers), cell, false, encoding);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CELL: Cell = Cell { row: 2, column: 4 };
+
+    fn encoded(code: u8, cell: Cell, release: bool, encoding: MouseEncoding) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode(&mut out, code, cell, release, encoding);
+        out
+    }
+
+    fn reporting(tracking: MouseTracking) -> Modes {
+        Modes {
+            mouse_tracking: tracking,
+            mouse_encoding: MouseEncoding::Sgr,
+            ..Modes::new()
+        }
+    }
+
+    /// A position in the middle of cell `column`, `row`.
+    fn at(column: usize, row: usize) -> Pos2 {
+        Pos2::new(column as f32 + 0.5, row as f32 + 0.5)
+    }
+
+    fn button(pos: Pos2, pressed: bool, modifiers: Modifiers) -> Event {
+        Event::PointerButton {
+            pos,
+            button: PointerButton::Primary,
+            pressed,
+            modifiers,
+        }
+    }
+
+    /// Feeds `events` to a new [`MouseState`] on a grid of one point wide
+    /// cells, returning the reports and whether each event was reported.
+    fn handle(modes: &Modes, events: &[Event]) -> (Vec<u8>, Vec<bool>) {
+        let mut state = MouseState::new();
+        let mut out = Vec::new();
+        let to_cell = |pos: Pos2| {
+            Some(Cell {
+                row: pos.y as usize,
+                column: pos.x as usize,
+            })
+        };
+        let handled = events
+            .iter()
+            .map(|event| state.handle(event, modes, 1.0, to_cell, &mut out))
+            .collect();
+        (out, handled)
+    }
+
+    #[test]
+    fn legacy_encoding() {
+        let default = MouseEncoding::Default;
+        assert_eq!(encoded(0, CELL, false, default), b"\x1b[M %#");
+        assert_eq!(encoded(2, CELL, false, default), b"\x1b[M\"%#");
+        // releases don't say which button
+        assert_eq!(encoded(2, CELL, true, default), b"\x1b[M#%#");
+        assert_eq!(encoded(16 | 1, CELL, true, default), b"\x1b[M3%#");
+    }
+
+    #[test]
+    fn legacy_coordinates_stop_at_223() {
+        let far = |column| Cell { row: 0, column };
+        let default = MouseEncoding::Default;
+        assert_eq!(encoded(0, far(221), false, default), b"\x1b[M \xfe!");
+        assert_eq!(encoded(0, far(222), false, default), b"\x1b[M \xff!");
+        assert_eq!(encoded(0, far(500), false, default), b"\x1b[M \xff!");
+        // UTF-8 goes further, as two byte characters
+        assert_eq!(
+            encoded(0, far(500), false, MouseEncoding::Utf8),
+            "\x1b[M \u{215}!".as_bytes()
+        );
+    }
+
+    #[test]
+    fn sgr_encoding() {
+        let sgr = MouseEncoding::Sgr;
+        assert_eq!(encoded(0, CELL, false, sgr), b"\x1b[<0;5;3M");
+        assert_eq!(encoded(2, CELL, true, sgr), b"\x1b[<2;5;3m");
+        let far = Cell {
+            row: 999,
+            column: 1999,
+        };
+        assert_eq!(encoded(WHEEL_UP, far, false, sgr), b"\x1b[<64;2000;1000M");
+    }
+
+    #[test]
+    fn x10_reports_presses_without_modifiers() {
+        let press = button(at(4, 2), true, Modifiers::CTRL);
+        let release = button(at(4, 2), false, Modifiers::CTRL);
+        let (out, handled) = handle(&reporting(MouseTracking::X10), &[press, release]);
+        assert_eq!(out, b"\x1b[<0;5;3M");
+        assert_eq!(handled, [true, true]);
+    }
+
+    #[test]
+    fn normal_tracking_reports_modifiers_and_releases() {
+        let modes = reporting(MouseTracking::Normal);
+        let press = button(at(4, 2), true, Modifiers::CTRL);
+        let release = button(at(6, 2), false, Modifiers::CTRL);
+        let (out, _) = handle(&modes, &[press, release]);
+        assert_eq!(out, b"\x1b[<16;5;3M\x1b[<16;7;3m");
+
+        // Shift is kept for selecting text locally
+        let (out, handled) = handle(&modes, &[button(at(4, 2), true, Modifiers::SHIFT)]);
+        assert_eq!(out, b"");
+        assert_eq!(handled, [false]);
+    }
+
+    #[test]
+    fn motion_sets_the_motion_bit() {
+        let modes = reporting(MouseTracking::ButtonEvent);
+        let moved = Event::PointerMoved(at(5, 2));
+        let (out, handled) = handle(&modes, std::slice::from_ref(&moved));
+        assert_eq!(out, b"");
+        assert_eq!(handled, [false]);
+
+        let press = button(at(4, 2), true, Modifiers::NONE);
+        let (out, _) = handle(&modes, &[press, moved.clone(), moved.clone()]);
+        assert_eq!(out, b"\x1b[<0;5;3M\x1b[<32;6;3M");
+
+        let (out, handled) = handle(&reporting(MouseTracking::AnyEvent), &[moved]);
+        assert_eq!(out, b"\x1b[<35;6;3M");
+        assert_eq!(handled, [true]);
+    }
+
+    #[test]
+    fn wheel_reports_whole_lines() {
+        let wheel = |delta| Event::MouseWheel {
+            unit: MouseWheelUnit::Line,
+            delta: egui::vec2(0.0, delta),
+            modifiers: Modifiers::NONE,
+        };
+        let modes = reporting(MouseTracking::Normal);
+        let moved = Event::PointerMoved(at(4, 2));
+        let (out, _) = handle(&modes, &[moved, wheel(0.5), wheel(0.5), wheel(-1.0)]);
+        assert_eq!(out, b"\x1b[<64;5;3M\x1b[<65;5;3M");
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    io::{self, Read, Write},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread::JoinHandle,
+};
+
+use portable_pty::{Child, CommandBuilder, MasterPty, PtySize, native_pty_system};
+
+pub use portable_pty::ExitStatus;
+
+use crate::{Terminal, kind::TerminalKind};
+
+/// A child process running on a pseudo-terminal.
+///
+/// Call [`PtySession::pump`] once per frame to move the child's output into a
+/// terminal and the terminal's output (key presses, query replies) back to the
+/// child.
+pub struct PtySession {
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    output: Receiver<Vec<u8>>,
+    reader: Option<JoinHandle<()>>,
+    size: (u16, u16),
+    eof: bool,
+}
+
+impl PtySession {
+    /// Spawns `cmd` on a new pseudo-terminal of `rows`x`columns`. If `ctx` is
+    /// given a repaint is requested whenever the child writes something.
+    pub fn spawn(
+        mut cmd: CommandBuilder,
+        rows: u16,
+        columns: u16,
+        ctx: Option<egui::Context>,
+    ) -> io::Result<Self> {
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows,
+                cols: columns,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(io::Error::other)?;
+
+        if cmd.get_env("TERM").is_none() {
+            cmd.env("TERM", "xterm-256color");
+        }
+        let child = pair.slave.spawn_command(cmd).map_err(io::Error::other)?;
+        // the child holds its own copy, ours would keep the pty open after it exits
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader().map_err(io::Error::other)?;
+        let writer = pair.master.take_writer().map_err(io::Error::other)?;
+
+        let (tx, output) = mpsc::channel();
+        let reader = std::thread::Builder::new()
+            .name("egui-ansi pty reader".into())
+            .spawn(move || {
+                let mut buf = [0u8; 4096];
+                loop {
+                    match reader.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            if tx.send(buf[..n].to_vec()).is_err() {
+                                break;
+                            }
+                            if let Some(ctx) = &ctx {
+                                ctx.request_repaint();
+                            }
+                        }
+                    }
+                }
+                if let Some(ctx) = &ctx {
+                    ctx.request_repaint();
+                }
+            })?;
+
+        Ok(Self {
+            master: pair.master,
+            writer,
+            child,
+            output,
+            reader: Some(reader),
+            size: (rows, columns),
+            eof: false,
+        })
+    }
+
+    /// Spawns the user's `$SHELL`, falling back to `/bin/sh`.
+    pub fn shell(rows: u16, columns: u16, ctx: Option<egui::Context>) -> io::Result<Self> {
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".into());
+        Self::spawn(CommandBuilder::new(shell), rows, columns, ctx)
+    }
+
+    /// Feeds everything the child wrote into `term`, forwards everything
+    /// queued on `term` to the child and keeps the pty size in sync with it.
+    pub fn pump<K: TerminalKind>(&mut self, term: &mut Terminal<K>) -> io::Result<()> {
+        loop {
+            match self.output.try_recv() {
+                Ok(bytes) => term.write_bytes(&bytes),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.eof = true;
+                    break;
+                }
+            }
+        }
+
+        if term.has_output() {
+            self.write(&term.take_output())?;
+        }
+
+        let size = term.size();
+        let rows = size.rows.min(u16::MAX as usize) as u16;
+        let columns = size.columns.min(u16::MAX as usize) as u16;
+        self.resize(rows, columns)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.writer.flush()
+    }
+
+    /// Resizes the pty, the child gets a `SIGWINCH` if the size changed.
+    pub fn resize(&mut self, rows: u16, columns: u16) -> io::Result<()> {
+        if self.size == (rows, columns) {
+            return Ok(());
+        }
+        self.size = (rows, columns);
+        self.master
+            .resize(PtySize {
+                rows,
+                cols: columns,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(io::Error::other)
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    /// `true` once the child closed its side of the pty and all of its output
+    /// has been pumped.
+    pub fn is_eof(&self) -> bool {
+        self.eof
+    }
+
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.child.try_wait()
+    }
+
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.child.kill()
+    }
+}
+
+impl Drop for PtySession {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            _ = self.child.kill();
+        }
+        _ = self.child.wait();
+        // a grandchild may still hold the pty open, don't wait on the reader
+        drop(self.reader.take());
+    }
+}
@@ -0,0 +1,478 @@
+use std::ops::Range;
+
+use egui::{Key, text::LayoutJob};
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    Config,
+    kind::{render::overlay, scrollback::Scrollback},
+};
+
+#[derive(Debug, Default)]
+pub struct Search {
+    pub bar_open: bool,
+
+    query: String,
+    regex: bool,
+    case_sensitive: bool,
+
+    compiled: Option<Regex>,
+    error: Option<String>,
+
+    text: String,
+    rows: Vec<Range<usize>>,
+    /// Revision and soft-wrap of every row in [`Search::text`], to find the
+    /// first one that changed since the last search.
+    searched_rows: Vec<(u64, bool)>,
+    /// [`Scrollback::dropped`] at the last search.
+    dropped: u64,
+    /// Rows which were archived at the last search, archived rows never
+    /// change so they aren't read again.
+    archived: usize,
+    /// Byte offset into [`Search::text`] up to which it was matched.
+    matched_to: usize,
+    matches: Vec<Range<usize>>,
+    current: Option<usize>,
+    searched_generation: Option<u64>,
+    scroll_pending: bool,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn is_regex(&self) -> bool {
+        self.regex
+    }
+
+    pub fn is_case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
+    pub fn set_query(&mut self, query: impl Into<String>, regex: bool) {
+        self.query = query.into();
+        self.regex = regex;
+        self.invalidate();
+    }
+
+    pub fn set_case_sensitive(&mut self, case_sensitive: bool) {
+        self.case_sensitive = case_sensitive;
+        self.invalidate();
+    }
+
+    pub fn clear(&mut self) {
+        self.query.clear();
+        self.invalidate();
+    }
+
+    /// The error produced while compiling the current query as a regex, if any.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// The logical text that was searched, soft-wrapped rows are joined
+    /// back together. Empty while there is no query.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Byte ranges of every match in [`Search::text`].
+    pub fn matches(&self) -> &[Range<usize>] {
+        &self.matches
+    }
+
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn current_match(&self) -> Option<Range<usize>> {
+        self.current.and_then(|i| self.matches.get(i).cloned())
+    }
+
+    pub fn next(&mut self) {
+        if self.matches.is_empty() {
+            return;
+        }
+        self.current = Some(self.current.map_or(0, |c| (c + 1) % self.matches.len()));
+        self.scroll_pending = true;
+    }
+
+    pub fn previous(&mut self) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let len = self.matches.len();
+        self.current = Some(self.current.map_or(len - 1, |c| (c + len - 1) % len));
+        self.scroll_pending = true;
+    }
+
+    fn invalidate(&mut self) {
+        self.compiled = None;
+        self.error = None;
+        self.matches.clear();
+        self.matched_to = 0;
+        self.current = None;
+        self.searched_generation = None;
+    }
+
+    fn compile(&mut self) {
+        let pattern = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        match RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .multi_line(true)
+            .build()
+        {
+            Ok(regex) => self.compiled = Some(regex),
+            Err(err) => self.error = Some(err.to_string()),
+        }
+    }
+
+    /// Brings the text and matches up to date with `lines`. Only rows that
+    /// changed since the last search are read and matched again.
+    pub(crate) fn update(&mut self, lines: &Scrollback, generation: u64) {
+        if self.searched_generation == Some(generation) {
+            return;
+        }
+        self.searched_generation = Some(generation);
+        if !self.query.is_empty() && self.compiled.is_none() && self.error.is_none() {
+            self.compile();
+        }
+        if self.query.is_empty() || self.compiled.is_none() {
+            // don't hold on to a copy of the scrollback, or read the archive,
+            // while there is nothing to look for
+            self.forget();
+            return;
+        }
+        self.forget_dropped(lines);
+        let changed = self.read_changed(lines);
+        let Some(regex) = &self.compiled else {
+            return;
+        };
+
+        // matches reaching into the logical line the changes start on could
+        // turn out different now
+        let line_start = self.text[..changed].rfind('\n').map_or(0, |i| i + 1);
+        let kept = self.matches.partition_point(|m| m.end < line_start);
+        let mut at = self
+            .matches
+            .get(kept)
+            .map_or(line_start, |m| m.start.min(line_start))
+            .min(self.matched_to);
+        self.matches.truncate(kept);
+        while let Some(m) = regex.find_at(&self.text, at) {
+            if m.is_empty() {
+                match self.text[m.end()..].chars().next() {
+                    Some(c) => at = m.end() + c.len_utf8(),
+                    None => break,
+                }
+                continue;
+            }
+            at = m.end();
+            self.matches.push(m.range());
+        }
+        self.matched_to = self.text.len();
+
+        self.current = match self.current {
+            _ if self.matches.is_empty() => None,
+            Some(current) => Some(current.min(self.matches.len() - 1)),
+            None => {
+                // logs grow downwards so the most recent match is the most interesting one
+                self.scroll_pending = true;
+                Some(self.matches.len() - 1)
+            }
+        };
+    }
+
+    /// Frees the searched text, it's read again once there is a query.
+    fn forget(&mut self) {
+        self.text = String::new();
+        self.rows = Vec::new();
+        self.searched_rows = Vec::new();
+        self.archived = 0;
+        self.matches.clear();
+        self.matched_to = 0;
+        self.current = None;
+    }
+
+    /// Removes the rows [`Scrollback::dropped`] since the last search from
+    /// the top, along with their matches.
+    fn forget_dropped(&mut self, lines: &Scrollback) {
+        let dropped = lines.dropped().saturating_sub(self.dropped);
+        self.dropped = lines.dropped();
+        let dropped = usize::try_from(dropped).map_or(self.rows.len(), |d| d.min(self.rows.len()));
+        self.archived = self.archived.saturating_sub(dropped);
+        if dropped == 0 {
+            return;
+        }
+
+        let cut = self
+            .rows
+            .get(dropped)
+            .map_or(self.text.len(), |row| row.start);
+        self.text.drain(..cut);
+        self.rows.drain(..dropped);
+        self.searched_rows.drain(..dropped);
+        for row in &mut self.rows {
+            *row = row.start - cut..row.end - cut;
+        }
+        self.matched_to = self.matched_to.saturating_sub(cut);
+
+        let gone = self.matches.partition_point(|m| m.start < cut);
+        self.matches.drain(..gone);
+        for m in &mut self.matches {
+            *m = m.start - cut..m.end - cut;
+        }
+        // the current match stays selected, or the oldest one if it was dropped
+        self.current = self.current.map(|current| current.saturating_sub(gone));
+    }
+
+    /// Reads the rows from the first one that changed since the last search
+    /// on into the text, returning the byte offset they start at.
+    fn read_changed(&mut self, lines: &Scrollback) -> usize {
+        let total = lines.total_rows();
+        let known = self.searched_rows.len().min(total);
+        let first = (self.archived.min(known)..known)
+            .find(|&i| {
+                lines
+                    .row(i)
+                    .is_none_or(|row| (row.revision, row.wrapped) != self.searched_rows[i])
+            })
+            .unwrap_or(known);
+
+        let start = self
+            .rows
+            .get(first)
+            .map_or(self.text.len(), |row| row.start);
+        self.text.truncate(start);
+        self.rows.truncate(first);
+        self.searched_rows.truncate(first);
+        self.matched_to = self.matched_to.min(start);
+        for line in (first..total).filter_map(|i| lines.row(i)) {
+            let row_start = self.text.len();
+            self.text.push_str(&line.text);
+            self.rows.push(row_start..self.text.len());
+            self.searched_rows.push((line.revision, line.wrapped));
+            if !line.wrapped {
+                self.text.push('\n');
+            }
+        }
+        self.archived = lines.archived();
+        start
+    }
+
+    /// The parts of matches which fall on `row`, relative to the row, and
+    /// whether they belong to the current match.
+    pub(crate) fn row_highlights(&self, row: usize) -> Vec<(Range<usize>, bool)> {
+        let Some(bounds) = self.rows.get(row) else {
+            return Vec::new();
+        };
+        let first = self.matches.partition_point(|m| m.end <= bounds.start);
+        self.matches[first..]
+            .iter()
+            .enumerate()
+            .take_while(|(_, m)| m.start < bounds.end)
+            .map(|(i, m)| {
+                let start = m.start.max(bounds.start) - bounds.start;
+                let end = m.end.min(bounds.end) - bounds.start;
+                (start..end, Some(first + i) == self.current)
+            })
+            .filter(|(range, _)| !range.is_empty())
+            .collect()
+    }
+
+    pub(crate) fn highlight_row(
+        job: &mut LayoutJob,
+        highlights: &[(Range<usize>, bool)],
+        cfg: &Config,
+    ) {
+        let ranges: Vec<_> = highlights.iter().map(|(range, _)| range.clone()).collect();
+        overlay(job, &ranges, |i, format| {
+            format.background = if highlights[i].1 {
+                cfg.search_current_match
+            } else {
+                cfg.search_match
+            };
+        });
+    }
+
+    /// The row the current match starts on, if the view should be scrolled
+    /// to it this frame.
+    pub(crate) fn take_scroll_target(&mut self) -> Option<usize> {
+        if !std::mem::take(&mut self.scroll_pending) {
+            return None;
+        }
+        let range = self.current_match()?;
+        Some(
+            self.rows
+                .partition_point(|row| row.start <= range.start)
+                .saturating_sub(1),
+        )
+    }
+
+    pub fn show_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let edit = ui.add(
+                egui::TextEdit::singleline(&mut self.query)
+                    .hint_text("Find")
+                    .desired_width(200.0),
+            );
+            let mut changed = edit.changed();
+            changed |= ui
+                .toggle_value(&mut self.regex, ".*")
+                .on_hover_text("Regular expression")
+                .changed();
+            changed |= ui
+                .toggle_value(&mut self.case_sensitive, "Aa")
+                .on_hover_text("Match case")
+                .changed();
+            if changed {
+                self.invalidate();
+            }
+
+            if edit.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                if ui.input(|i| i.modifiers.shift) {
+                    self.previous();
+                } else {
+                    self.next();
+                }
+                edit.request_focus();
+            }
+
+            if ui.button("⏶").on_hover_text("Previous match").clicked() {
+                self.previous();
+            }
+            if ui.button("⏷").on_hover_text("Next match").clicked() {
+                self.next();
+            }
+
+            if let Some(error) = &self.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            } else if !self.query.is_empty() {
+                ui.label(match self.current {
+                    Some(current) => format!("{}/{}", current + 1, self.matches.len()),
+                    None => "No results".to_owned(),
+                });
+            }
+
+            if ui.button("✖").clicked() {
+                self.bar_open = false;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kind::{line::Line, scrollback::ScrollbackLimit, style::StyleId};
+
+    const LIMIT: ScrollbackLimit = ScrollbackLimit::Lines(100);
+
+    fn line(text: &str) -> Line {
+        let mut line = Line::default();
+        push(&mut line, text);
+        line
+    }
+
+    fn push(line: &mut Line, text: &str) {
+        for c in text.chars() {
+            line.push(c, StyleId(0), None);
+        }
+    }
+
+    fn matched(search: &Search) -> Vec<&str> {
+        search
+            .matches()
+            .iter()
+            .map(|m| &search.text()[m.clone()])
+            .collect()
+    }
+
+    #[test]
+    fn appending_to_the_last_line_completes_a_match() {
+        let mut lines = Scrollback::new();
+        lines.push_back(line("foo ba"), LIMIT);
+        let mut search = Search::new();
+        search.set_query("bar", false);
+        search.update(&lines, 0);
+        assert!(search.matches().is_empty());
+        assert_eq!(search.current(), None);
+
+        lines.edit_back(|line| push(line, "r"));
+        search.update(&lines, 1);
+        assert_eq!(search.text(), "foo bar\n");
+        assert_eq!(matched(&search), ["bar"]);
+        assert_eq!(search.current_match(), Some(4..7));
+    }
+
+    #[test]
+    fn evicted_rows_shift_the_matches() {
+        let limit = ScrollbackLimit::Lines(2);
+        let mut lines = Scrollback::new();
+        lines.push_back(line("a x"), limit);
+        lines.push_back(line("b x"), limit);
+        let mut search = Search::new();
+        search.set_query("x", false);
+        search.update(&lines, 0);
+        assert_eq!(search.matches(), [2..3, 6..7]);
+        assert_eq!(search.current(), Some(1));
+
+        lines.push_back(line("c x"), limit);
+        search.update(&lines, 1);
+        assert_eq!(search.text(), "b x\nc x\n");
+        assert_eq!(search.matches(), [2..3, 6..7]);
+        // still on the match in "b x"
+        assert_eq!(search.current(), Some(0));
+        assert_eq!(search.row_highlights(0), [(2..3, true)]);
+        assert_eq!(search.row_highlights(1), [(2..3, false)]);
+    }
+
+    #[test]
+    fn matches_span_soft_wrapped_rows() {
+        let mut lines = Scrollback::new();
+        let mut first = line("hel");
+        first.wrapped = true;
+        lines.push_back(first, LIMIT);
+        lines.push_back(line("lo"), LIMIT);
+        let mut search = Search::new();
+        search.set_query("hello", false);
+        search.update(&lines, 0);
+        assert_eq!(search.text(), "hello\n");
+        assert_eq!(matched(&search), ["hello"]);
+        assert_eq!(search.row_highlights(0), [(0..3, true)]);
+        assert_eq!(search.row_highlights(1), [(0..2, true)]);
+    }
+
+    #[test]
+    fn clearing_the_query_frees_the_text() {
+        let mut lines = Scrollback::new();
+        lines.push_back(line("one"), LIMIT);
+        lines.push_back(line("two"), LIMIT);
+        let mut search = Search::new();
+        search.set_query("o", false);
+        search.update(&lines, 0);
+        assert_eq!(matched(&search), ["o", "o"]);
+
+        search.clear();
+        search.update(&lines, 1);
+        assert_eq!(search.text(), "");
+        assert!(search.matches().is_empty());
+        assert_eq!(search.current(), None);
+        assert!(search.row_highlights(0).is_empty());
+
+        search.set_query("t", false);
+        search.update(&lines, 2);
+        assert_eq!(search.text(), "one\ntwo\n");
+        assert_eq!(matched(&search), ["t"]);
+        assert_eq!(search.current_match(), Some(4..5));
+    }
+}
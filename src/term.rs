@@ -1,18 +1,66 @@
 use std::{
+    hash::{DefaultHasher, Hash, Hasher},
     ops::{Deref, DerefMut},
     rc::Rc,
     sync::Arc,
 };
 
 use ansi::AnsiParser;
-use egui::text::LayoutJob;
+use egui::{Galley, text::LayoutJob};
 
-use crate::{Config, kind::TerminalKind};
+use crate::{
+    Config, Renderer,
+    bell::Bell,
+    clipboard::{Clipboard, ClipboardRequest},
+    grid,
+    handle::{self, TerminalHandle},
+    input,
+    kind::{
+        TerminalKind,
+        line::Line,
+        modes::{Modes, MouseTracking},
+        render,
+        render::Blink,
+        sequence::{Recorder, Sequence},
+    },
+    links::{Hovered, LinkKind, LinkRule, Links},
+    mouse::{Cell, MouseState},
+    search::Search,
+    view::{RowCache, RowKey},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalSize {
+    pub rows: usize,
+    pub columns: usize,
+}
+
+impl TerminalSize {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            rows: cfg.rows,
+            columns: cfg.columns,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct GenericTerminal<K: TerminalKind, T: ?Sized> {
     state: K,
+    size: TerminalSize,
+    size_changed: bool,
     pub cfg: Config,
+    pub search: Search,
+    generation: u64,
+    output: Vec<u8>,
+    mouse: MouseState,
+    rows: RowCache,
+    sequences: Recorder,
+    handle: Arc<handle::Shared>,
+    links: Links,
+    clipboard: Clipboard,
+    bell: Bell,
+    seen_title: u64,
     pub ansi: ansi::AnsiParser<T>,
 }
 
@@ -36,43 +84,77 @@ impl<K: TerminalKind, const C: usize> DerefMut for GenericTerminal<K, [u8; C]> {
 impl<K: TerminalKind> GenericTerminal<K, [u8]> {
     #[must_use]
     pub fn new_box<const C: usize>(cfg: Config) -> Box<Self> {
-        Box::new(GenericTerminal {
-            state: K::new(&cfg),
-            cfg,
-            ansi: AnsiParser::<[u8; C]>::new(),
-        })
+        Box::new(Self::with_kind::<C>(K::new(&cfg), cfg))
     }
 
     #[must_use]
     pub fn new_rc<const C: usize>(cfg: Config) -> Rc<Self> {
-        Rc::new(GenericTerminal {
-            state: K::new(&cfg),
-            cfg,
-            ansi: AnsiParser::<[u8; C]>::new(),
-        })
+        Rc::new(Self::with_kind::<C>(K::new(&cfg), cfg))
     }
 
     #[must_use]
     pub fn new_arc<const C: usize>(cfg: Config) -> Arc<Self> {
-        Arc::new(GenericTerminal {
-            state: K::new(&cfg),
-            cfg,
-            ansi: AnsiParser::<[u8; C]>::new(),
-        })
+        Arc::new(Self::with_kind::<C>(K::new(&cfg), cfg))
     }
 
     #[must_use]
     pub fn new_static<const C: usize>(cfg: Config) -> StaticTerminal<K, C> {
+        Self::with_kind(K::new(&cfg), cfg)
+    }
+
+    /// A terminal showing `state`, which the other constructors put behind
+    /// the pointer they return.
+    fn with_kind<const C: usize>(state: K, cfg: Config) -> StaticTerminal<K, C> {
         GenericTerminal {
-            state: K::new(&cfg),
+            state,
+            size: TerminalSize::from_config(&cfg),
+            size_changed: false,
             cfg,
+            search: Search::new(),
+            generation: 0,
+            output: Vec::new(),
+            mouse: MouseState::new(),
+            rows: RowCache::default(),
+            sequences: Recorder::new(),
+            handle: Arc::default(),
+            links: Links::default(),
+            clipboard: Clipboard::default(),
+            bell: Bell::default(),
+            seen_title: 0,
             ansi: AnsiParser::<[u8; C]>::new(),
         }
     }
 
     pub fn write_bytes(&mut self, bytes: &[u8]) {
         for b in bytes {
+            if *b == 0x07 && self.sequences.is_ground() {
+                self.bell.ring(&self.cfg);
+            }
             self.state.march(self.ansi.next(*b), &self.cfg);
+            if let Some(seq) = self.sequences.push(*b) {
+                if let Sequence::Osc(osc) = seq
+                    && osc.command == 52
+                {
+                    self.clipboard
+                        .osc(&osc, self.cfg.clipboard, &mut self.output);
+                }
+                self.state.sequence(seq, &self.cfg);
+            }
+        }
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// A handle other threads can write into, see [`TerminalHandle`].
+    pub fn handle(&self) -> TerminalHandle {
+        TerminalHandle::new(self.handle.clone())
+    }
+
+    /// Feeds everything written through handles into the terminal, this
+    /// happens on its own whenever the terminal is shown.
+    pub fn drain_handles(&mut self) {
+        let pending = self.handle.take();
+        if !pending.is_empty() {
+            self.write_bytes(&pending);
         }
     }
 
@@ -80,36 +162,497 @@ impl<K: TerminalKind> GenericTerminal<K, [u8]> {
         egui::Frame::new()
             .inner_margin(2)
             .corner_radius(ui.style().visuals.widgets.noninteractive.corner_radius)
-            .fill(self.cfg.bg_default)
+            .fill(self.background())
             .stroke(ui.style().visuals.window_stroke())
             .show(ui, |ui| {
-                egui::ScrollArea::both()
-                    .stick_to_bottom(true)
-                    .stick_to_right(true)
-                    .show(ui, |ui| {
-                        ui.label(self.layout(ui.ctx()));
-                        ui.allocate_space(ui.available_size());
-                    });
+                self.show_contents(ui);
             });
     }
 
     pub fn show_framed(&mut self, ui: &mut egui::Ui) {
         egui::Frame::new()
             .inner_margin(2)
-            .fill(self.cfg.bg_default)
+            .fill(self.background())
             .show(ui, |ui| {
-                egui::ScrollArea::both()
-                    .stick_to_bottom(true)
-                    .stick_to_right(true)
-                    .show(ui, |ui| {
-                        ui.label(self.layout(ui.ctx()));
-                        ui.allocate_space(ui.available_size());
+                self.show_contents(ui);
+            });
+    }
+
+    /// The default background, which DECSCNM swaps with the foreground.
+    fn background(&self) -> egui::Color32 {
+        if self.state.modes().reverse_video {
+            self.cfg.fg_default
+        } else {
+            self.cfg.bg_default
+        }
+    }
+
+    fn show_contents(&mut self, ui: &mut egui::Ui) {
+        self.handle.attach(ui.ctx());
+        self.drain_handles();
+        if self.search.bar_open {
+            self.search.show_bar(ui);
+        }
+        if self.cfg.fit_to_widget {
+            let cell = self.cfg.cell_size(ui.ctx());
+            let available = ui.available_size();
+            self.resize(TerminalSize {
+                rows: ((available.y / cell.y) as usize).max(1),
+                columns: ((available.x / cell.x) as usize).max(1),
+            });
+        }
+        let row_height = self.cfg.cell_size(ui.ctx()).y;
+        self.search.update(self.state.lines(), self.generation);
+        let scroll_to = self.search.take_scroll_target();
+        let blink = Blink::now(&self.cfg, ui.ctx());
+        self.rows.begin_frame(&self.cfg);
+
+        ui.spacing_mut().item_spacing.y = 0.0;
+        let output = egui::ScrollArea::both()
+            .auto_shrink(false)
+            .stick_to_bottom(true)
+            .stick_to_right(true)
+            .show_rows(ui, row_height, self.state.lines().total_rows(), |ui, range| {
+                let origin = ui.cursor().min - egui::vec2(0.0, range.start as f32 * row_height);
+                // the scroll area scrolls once its contents are shown, so this
+                // has to happen in here to keep the wheel from the program
+                if self.handle_mouse(ui, ui.clip_rect(), origin) {
+                    ui.input_mut(|i| {
+                        i.smooth_scroll_delta = egui::Vec2::ZERO;
+                        i.raw_scroll_delta = egui::Vec2::ZERO;
                     });
+                    ui.style_mut().interaction.selectable_labels = false;
+                }
+                let mut used = Blink::default();
+                for row in range {
+                    match self.cfg.renderer {
+                        Renderer::Text => {
+                            let galley =
+                                self.row_galley(ui.ctx(), row, blink, row_height, &mut used);
+                            ui.label(galley);
+                        }
+                        Renderer::Grid => self.paint_row(ui, row, blink, &mut used),
+                    }
+                }
+                used.request_repaint(&self.cfg, ui.ctx());
+
+                if let Some(row) = scroll_to {
+                    let rect = egui::Rect::from_min_size(
+                        origin + egui::vec2(0.0, row as f32 * row_height),
+                        egui::vec2(1.0, row_height),
+                    );
+                    ui.scroll_to_rect(rect, Some(egui::Align::Center));
+                }
+                origin
             });
+
+        let response = ui.interact(
+            output.inner_rect,
+            output.id.with("terminal"),
+            egui::Sense::click(),
+        );
+        self.handle_links(ui, &response, output.inner);
+        self.handle_input(ui, &response);
+        self.bell.flash(ui, output.inner_rect, &self.cfg);
+        if let Some(text) = self.clipboard.take_copy() {
+            ui.ctx().copy_text(text);
+        }
+    }
+
+    /// Lays out a single row, or reuses its galley from the last frame if
+    /// nothing it depends on changed.
+    fn row_galley(
+        &mut self,
+        ctx: &egui::Context,
+        row: usize,
+        blink: Blink,
+        row_height: f32,
+        used: &mut Blink,
+    ) -> Arc<Galley> {
+        let line = self.state.lines().row(row).unwrap_or_default();
+        let line = line.as_ref();
+        let uses = Blink::of(line, self.state.styles());
+        *used = used.union(uses);
+
+        let cursor = self
+            .state
+            .cursor()
+            .filter(|cursor| cursor.row + self.state.lines().archived() == row)
+            .map(|cursor| cursor.byte);
+        let highlights = self.search.row_highlights(row);
+        let hovered = self.hovered_ranges(row, line);
+        let reverse = self.state.modes().reverse_video;
+        let key = RowKey {
+            revision: line.revision(),
+            blink: blink.intersection(uses),
+            cursor,
+            decorations: {
+                let mut hasher = DefaultHasher::new();
+                highlights.hash(&mut hasher);
+                hovered.hash(&mut hasher);
+                reverse.hash(&mut hasher);
+                hasher.finish()
+            },
+            pixels_per_point: ctx.pixels_per_point().to_bits(),
+        };
+
+        self.rows.get_or_layout(key, || {
+            let mut job = render::row_job(
+                line,
+                self.state.styles(),
+                cursor,
+                &self.cfg,
+                blink,
+                row_height,
+            );
+            Search::highlight_row(&mut job, &highlights, &self.cfg);
+            render::overlay(&mut job, &hovered, |_, format| {
+                format.underline = egui::Stroke::new(self.cfg.underline_width, format.color);
+            });
+            if reverse {
+                for section in &mut job.sections {
+                    render::reverse_screen(&mut section.format, &self.cfg);
+                }
+            }
+            ctx.fonts(|f| f.layout_job(job))
+        })
+    }
+
+    /// Paints a single row cell by cell with [`Renderer::Grid`].
+    fn paint_row(&self, ui: &mut egui::Ui, row: usize, blink: Blink, used: &mut Blink) {
+        let line = self.state.lines().row(row).unwrap_or_default();
+        let line = line.as_ref();
+        let cell = self.cfg.cell_size(ui.ctx());
+        // one extra cell so a cursor at the end of the row fits
+        let columns = line.width(self.cfg.emoji_width) + 1;
+        let (rect, _) = ui.allocate_exact_size(
+            egui::vec2(columns as f32 * cell.x, cell.y),
+            egui::Sense::hover(),
+        );
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+
+        let cursor = self
+            .state
+            .cursor()
+            .filter(|cursor| cursor.row + self.state.lines().archived() == row)
+            .map(|cursor| cursor.byte);
+        let highlights = self.search.row_highlights(row);
+        let hovered = self.hovered_ranges(row, line);
+        let uses = grid::paint_row(
+            ui.painter(),
+            rect.min,
+            cell,
+            line,
+            self.state.styles(),
+            grid::RowDecorations {
+                cursor,
+                highlights: &highlights,
+                underline: &hovered,
+                blink,
+                reverse: self.state.modes().reverse_video,
+            },
+            &self.cfg,
+        );
+        *used = used.union(uses);
+    }
+
+    /// Byte ranges of `line`, which is row `row`, belonging to the hovered
+    /// link.
+    fn hovered_ranges(&self, row: usize, line: &Line) -> Vec<std::ops::Range<usize>> {
+        match &self.links.hovered {
+            Some(Hovered::Hyperlink(link)) => line.link_ranges(*link),
+            Some(Hovered::Detected {
+                row: hovered,
+                range,
+                ..
+            }) if *hovered == row => vec![range.clone()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The hyperlink at `pos`, or else the first text matching a link rule.
+    fn link_at(&self, pos: egui::Pos2, origin: egui::Pos2, cell: egui::Vec2) -> Option<Hovered> {
+        let rel = pos - origin;
+        if rel.x < 0.0 || rel.y < 0.0 {
+            return None;
+        }
+        let row = (rel.y / cell.y) as usize;
+        let line = self.state.lines().row(row)?;
+        let byte = line.byte_at_column((rel.x / cell.x) as usize, self.cfg.emoji_width)?;
+        if let Some(link) = line.link_at(byte) {
+            return Some(Hovered::Hyperlink(link));
+        }
+        let (range, kind) = self.links.detect(line.text(), byte)?;
+        Some(Hovered::Detected {
+            row,
+            text: line.text()[range.clone()].to_owned(),
+            range,
+            kind,
+        })
+    }
+
+    /// Underlines links under the pointer, shows where hyperlinks lead and
+    /// opens links on click. While the program tracks the mouse links only
+    /// react with shift held.
+    fn handle_links(&mut self, ui: &egui::Ui, response: &egui::Response, origin: egui::Pos2) {
+        let cell = self.cfg.cell_size(ui.ctx());
+        let local = self.state.modes().mouse_tracking == MouseTracking::Off
+            || ui.input(|i| i.modifiers.shift);
+        let hovered = response
+            .hover_pos()
+            .filter(|_| local)
+            .and_then(|pos| self.link_at(pos, origin, cell));
+        if hovered != self.links.hovered {
+            self.links.hovered = hovered.clone();
+            ui.ctx().request_repaint();
+        }
+
+        let Some(hovered) = hovered else {
+            return;
+        };
+        ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+        match hovered {
+            Hovered::Hyperlink(link) => {
+                let uri = self.state.links().get(link).to_owned();
+                if response.clicked() {
+                    self.links.open_hyperlink(ui.ctx(), &uri);
+                }
+                response.clone().on_hover_text_at_pointer(uri);
+            }
+            Hovered::Detected { text, kind, .. } => {
+                if response.clicked() {
+                    self.links.open_detected(ui.ctx(), &text, &kind);
+                }
+            }
+        }
+    }
+
+    /// Reports pointer events over `rect` to the program. Returns whether
+    /// any were reported, they shouldn't scroll or select text then.
+    fn handle_mouse(&mut self, ui: &egui::Ui, rect: egui::Rect, origin: egui::Pos2) -> bool {
+        let cell = self.cfg.cell_size(ui.ctx());
+        let top = self.state.lines().total_rows().saturating_sub(self.size.rows);
+        let to_cell = |pos: egui::Pos2| {
+            if !rect.contains(pos) {
+                return None;
+            }
+            let rel = pos - origin;
+            Some(Cell {
+                row: ((rel.y / cell.y).max(0.0) as usize).checked_sub(top)?,
+                column: (rel.x / cell.x).max(0.0) as usize,
+            })
+        };
+
+        let hovered = ui.rect_contains_pointer(rect);
+        let events = ui.input(|i| i.events.clone());
+        let mut reported = false;
+        for event in &events {
+            if matches!(event, egui::Event::MouseWheel { .. }) && !hovered {
+                continue;
+            }
+            reported |= self
+                .mouse
+                .handle(event, self.state.modes(), cell.y, to_cell, &mut self.output);
+        }
+        reported
+    }
+
+    fn handle_input(&mut self, ui: &egui::Ui, response: &egui::Response) {
+        if response.clicked() {
+            response.request_focus();
+        }
+        if self.state.modes().focus_reporting {
+            if response.gained_focus() {
+                self.output.extend_from_slice(b"\x1b[I");
+            } else if response.lost_focus() {
+                self.output.extend_from_slice(b"\x1b[O");
+            }
+        }
+        if !response.has_focus() {
+            return;
+        }
+        self.bell.acknowledge();
+        ui.memory_mut(|m| {
+            m.set_focus_lock_filter(
+                response.id,
+                egui::EventFilter {
+                    tab: true,
+                    horizontal_arrows: true,
+                    vertical_arrows: true,
+                    escape: true,
+                },
+            )
+        });
+
+        let (events, modifiers) = ui.input(|i| (i.events.clone(), i.modifiers));
+        for event in events {
+            match event {
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => {
+                    input::encode_key(key, modifiers, self.state.modes(), &mut self.output);
+                }
+                egui::Event::Text(text) => input::encode_text(&text, modifiers, &mut self.output),
+                egui::Event::Paste(text) => {
+                    self.clipboard.remember(&text);
+                    self.paste(&text);
+                }
+                // egui turns Ctrl+C/Ctrl+X into clipboard events before they reach us as keys
+                egui::Event::Copy if modifiers.ctrl && !modifiers.shift => self.output.push(0x03),
+                egui::Event::Cut if modifiers.ctrl && !modifiers.shift => self.output.push(0x18),
+                _ => {}
+            }
+        }
+    }
+
+    /// Queues bytes to be sent to the attached program, as if they were typed.
+    pub fn send(&mut self, bytes: &[u8]) {
+        self.output.extend_from_slice(bytes);
+    }
+
+    /// Sends `text` as a paste, bracketed if the program asked for it.
+    pub fn paste(&mut self, text: &str) {
+        // like xterm, line breaks are sent as carriage returns
+        let text = text.replace("\r\n", "\r").replace('\n', "\r");
+        if self.state.modes().bracketed_paste {
+            // don't let the pasted text end the bracket early
+            let text = text.replace("\x1b[201~", "");
+            self.output.extend_from_slice(b"\x1b[200~");
+            self.output.extend_from_slice(text.as_bytes());
+            self.output.extend_from_slice(b"\x1b[201~");
+        } else {
+            self.output.extend_from_slice(text.as_bytes());
+        }
+    }
+
+    /// Takes everything queued for the attached program since the last call,
+    /// key presses from the widget as well as replies to terminal queries.
+    #[must_use]
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn has_output(&self) -> bool {
+        !self.output.is_empty()
+    }
+
+    pub fn size(&self) -> TerminalSize {
+        self.size
+    }
+
+    /// The modes the program has set, like autowrap or bracketed paste.
+    pub fn modes(&self) -> &Modes {
+        self.state.modes()
+    }
+
+    /// The terminal state, to inspect its lines and styles directly.
+    pub fn kind(&self) -> &K {
+        &self.state
+    }
+
+    /// Compresses lines evicted from the scrollback into a temporary file
+    /// instead of dropping them, they're read back in when scrolled to or
+    /// searched.
+    #[cfg(feature = "archive")]
+    pub fn archive_scrollback(&mut self) -> std::io::Result<()> {
+        let archive = crate::kind::archive::Archive::new()?;
+        self.state.scrollback_mut().set_archive(archive);
+        Ok(())
+    }
+
+    /// Called with the URI of a clicked hyperlink instead of opening it in a
+    /// browser, returning `false` opens it anyway.
+    pub fn on_hyperlink(&mut self, on_hyperlink: impl FnMut(&str) -> bool + Send + 'static) {
+        self.links.set_on_hyperlink(on_hyperlink);
+    }
+
+    /// Called with the text and kind of a clicked link found by the link
+    /// rules. Returning `false` for a URL opens it in a browser.
+    pub fn on_link(&mut self, on_link: impl FnMut(&str, &LinkKind) -> bool + Send + 'static) {
+        self.links.set_on_link(on_link);
+    }
+
+    /// Decides whether a program may use the clipboard when
+    /// [`Config::clipboard`] is [`ClipboardPolicy::Ask`](crate::ClipboardPolicy::Ask).
+    pub fn on_clipboard_request(
+        &mut self,
+        ask: impl FnMut(&ClipboardRequest<'_>) -> bool + Send + 'static,
+    ) {
+        self.clipboard.set_ask(ask);
+    }
+
+    /// Called whenever the program rings the bell, to play a sound or ask for
+    /// the window's attention.
+    pub fn on_bell(&mut self, on_bell: impl FnMut() + Send + 'static) {
+        self.bell.set_on_bell(on_bell);
+    }
+
+    /// Bells rung since the terminal last had focus, for a badge on its tab.
+    pub fn unseen_bells(&self) -> u64 {
+        self.bell.unseen()
+    }
+
+    /// Clears [`GenericTerminal::unseen_bells`], this also happens whenever
+    /// the terminal has focus.
+    pub fn acknowledge_bells(&mut self) {
+        self.bell.acknowledge();
+    }
+
+    /// The rules links are detected with when hovering text, URLs and file
+    /// locations by default.
+    pub fn link_rules_mut(&mut self) -> &mut Vec<LinkRule> {
+        &mut self.links.rules
+    }
+
+    /// Calls `on_evict` with every line that falls out of the scrollback.
+    pub fn on_evict(&mut self, on_evict: impl FnMut(Line) + Send + 'static) {
+        self.state.scrollback_mut().on_evict(on_evict);
+    }
+
+    /// Resizes the grid, wrapping follows the new column count from then on.
+    pub fn resize(&mut self, size: TerminalSize) {
+        if self.size == size {
+            return;
+        }
+        self.size = size;
+        self.size_changed = true;
+        self.state.resize(size, &self.cfg);
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// Returns the new size if it changed since the last call.
+    pub fn take_size_change(&mut self) -> Option<TerminalSize> {
+        std::mem::take(&mut self.size_changed).then_some(self.size)
+    }
+
+    /// The window title set by the program through OSC 0/2.
+    pub fn title(&self) -> &str {
+        self.state.title().title()
+    }
+
+    /// The icon name set by the program through OSC 0/1.
+    pub fn icon_name(&self) -> &str {
+        self.state.title().icon_name()
+    }
+
+    /// Returns the new title if it changed since the last call.
+    pub fn take_title_change(&mut self) -> Option<&str> {
+        let revision = self.state.title().revision();
+        if revision == self.seen_title {
+            return None;
+        }
+        self.seen_title = revision;
+        Some(self.state.title().title())
     }
 
     pub fn clear(&mut self) {
         self.state.clear();
+        self.generation = self.generation.wrapping_add(1);
     }
 
     #[must_use]
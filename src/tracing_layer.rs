@@ -0,0 +1,172 @@
+use std::fmt::{self, Write};
+
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+    span,
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::{LevelColors, config::Severity, handle::TerminalHandle};
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+
+/// A `tracing` layer writing formatted events into a terminal.
+///
+/// ```ignore
+/// use tracing_subscriber::prelude::*;
+/// tracing_subscriber::registry()
+///     .with(TerminalLayer::new(term.handle()))
+///     .init();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TerminalLayer {
+    handle: TerminalHandle,
+    colors: LevelColors,
+    target: bool,
+}
+
+impl TerminalLayer {
+    pub fn new(handle: TerminalHandle) -> Self {
+        Self {
+            handle,
+            colors: LevelColors::default(),
+            target: true,
+        }
+    }
+
+    pub fn with_colors(mut self, colors: LevelColors) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// Whether the target of an event is written before its message.
+    pub fn with_target(mut self, target: bool) -> Self {
+        self.target = target;
+        self
+    }
+}
+
+impl From<&Level> for Severity {
+    fn from(level: &Level) -> Self {
+        match *level {
+            Level::ERROR => Self::Error,
+            Level::WARN => Self::Warn,
+            Level::INFO => Self::Info,
+            Level::DEBUG => Self::Debug,
+            Level::TRACE => Self::Trace,
+        }
+    }
+}
+
+/// The formatted fields of a span, kept in its extensions.
+struct SpanFields(String);
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    rest: String,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            _ = write!(self.message, "{value:?}");
+        } else {
+            if !self.rest.is_empty() {
+                self.rest.push(' ');
+            }
+            _ = write!(self.rest, "{ITALIC}{}{RESET}={value:?}", field.name());
+        }
+    }
+}
+
+impl Fields {
+    fn into_span_fields(self) -> String {
+        match (self.message.is_empty(), self.rest.is_empty()) {
+            (true, _) => self.rest,
+            (false, true) => self.message,
+            (false, false) => format!("{} {}", self.message, self.rest),
+        }
+    }
+}
+
+impl<S> Layer<S> for TerminalLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        span.extensions_mut()
+            .insert(SpanFields(fields.into_span_fields()));
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        let fields = fields.into_span_fields();
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanFields(existing)) = extensions.get_mut::<SpanFields>() {
+            if !existing.is_empty() {
+                existing.push(' ');
+            }
+            existing.push_str(&fields);
+        } else {
+            extensions.insert(SpanFields(fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let (color, level) = self.colors.level(metadata.level().into());
+        let mut out = format!("\x1b[1;{color}m{level}{RESET} ");
+
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                out.push_str("\x1b[1m");
+                out.push_str(span.name());
+                out.push_str(RESET);
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>()
+                    && !fields.is_empty()
+                {
+                    _ = write!(out, "{{{fields}}}");
+                }
+                out.push(':');
+            }
+            out.push(' ');
+        }
+        if self.target {
+            _ = write!(out, "{DIM}{}:{RESET} ", metadata.target());
+        }
+
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        out.push_str(&fields.message);
+        if !fields.rest.is_empty() {
+            if !fields.message.is_empty() {
+                out.push(' ');
+            }
+            out.push_str(&fields.rest);
+        }
+        // a bare LF keeps the column unless the program set LNM
+        out.push_str("\r\n");
+        self.handle.write_str(&out);
+    }
+}
@@ -0,0 +1,59 @@
+use std::{collections::HashMap, sync::Arc};
+
+use egui::Galley;
+
+use crate::{Config, kind::render::Blink};
+
+/// Everything a row's galley depends on besides the [`Config`], which
+/// [`RowCache::begin_frame`] checks for changes instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct RowKey {
+    pub(crate) revision: u64,
+    pub(crate) blink: Blink,
+    pub(crate) cursor: Option<usize>,
+    /// Hash of the search highlights and hovered link on the row, and
+    /// whether the screen is in reverse video.
+    pub(crate) decorations: u64,
+    pub(crate) pixels_per_point: u32,
+}
+
+/// Galleys of the rows shown last frame, anything that wasn't used in the
+/// previous frame is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct RowCache {
+    previous: HashMap<RowKey, Arc<Galley>>,
+    current: HashMap<RowKey, Arc<Galley>>,
+    /// The config the cached galleys were laid out with.
+    cfg: Option<Config>,
+}
+
+impl RowCache {
+    /// Starts a frame laid out with `cfg`, dropping every galley if it
+    /// changed since the last one.
+    pub(crate) fn begin_frame(&mut self, cfg: &Config) {
+        if self.cfg.as_ref() != Some(cfg) {
+            self.clear();
+            self.cfg = Some(cfg.clone());
+        }
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    pub(crate) fn get_or_layout(
+        &mut self,
+        key: RowKey,
+        layout: impl FnOnce() -> Arc<Galley>,
+    ) -> Arc<Galley> {
+        let galley = match self.current.get(&key) {
+            Some(galley) => galley.clone(),
+            None => self.previous.remove(&key).unwrap_or_else(layout),
+        };
+        self.current.insert(key, galley.clone());
+        galley
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.previous.clear();
+        self.current.clear();
+    }
+}